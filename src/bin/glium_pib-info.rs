@@ -0,0 +1,171 @@
+//! Prints the state of the graphics stack: the libraries, the displays, the EGL configs and the EGL and GL strings.
+//!
+//! Usage: glium_pib-info [--dispmanx | --kms | --surfaceless | --mock] [--lib-dir DIR]
+//!
+//! Without a platform the system is detected with `System::autodetect`.
+//! `--surfaceless` needs no display. `--mock` additionally forces the software renderer of Mesa,
+//! so it needs neither a display nor a GPU and can be used to test the tool anywhere.
+
+extern crate glium;
+extern crate glium_pib;
+
+use std::env;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+
+use glium::backend::Backend;
+use glium_pib::{System, Window, LibDir, KmsConfig, Display, Error};
+
+/// The platform selected on the command line.
+enum Platform {
+	Autodetect,
+	Dispmanx,
+	Kms,
+	Surfaceless,
+	/// Surfaceless with the software renderer of Mesa.
+	Mock,
+}
+
+fn main() {
+	let mut platform = Platform::Autodetect;
+	let mut lib_dir = None;
+	let mut args = env::args().skip(1);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--dispmanx" => platform = Platform::Dispmanx,
+			"--kms" => platform = Platform::Kms,
+			"--surfaceless" => platform = Platform::Surfaceless,
+			"--mock" => platform = Platform::Mock,
+			"--lib-dir" => match args.next() {
+				Some(dir) => lib_dir = Some(LibDir(PathBuf::from(dir))),
+				None => usage(),
+			},
+			_ => usage(),
+		}
+	}
+	match platform {
+		// Read by Mesa when the libraries are loaded.
+		Platform::Mock => env::set_var("LIBGL_ALWAYS_SOFTWARE", "1"),
+		_ => {},
+	}
+	match run(platform, lib_dir) {
+		Ok(()) => {},
+		Err(e) => {
+			let _ = writeln!(io::stderr(), "error: {}", e);
+			process::exit(1);
+		},
+	}
+}
+
+fn usage() -> ! {
+	let _ = writeln!(io::stderr(), "usage: glium_pib-info [--dispmanx | --kms | --surfaceless | --mock] [--lib-dir DIR]");
+	process::exit(2);
+}
+
+fn run(platform: Platform, lib_dir: Option<LibDir>) -> Result<(), Error> {
+	let system = match platform {
+		Platform::Autodetect => {
+			let (system, report) = try!(System::autodetect());
+			println!("Platform: {:?} (autodetected)", report.chosen);
+			for &(ref probe, ref error) in &report.failures {
+				println!("  {:?} failed: {}", probe, error);
+			}
+			system
+		},
+		Platform::Dispmanx => {
+			println!("Platform: dispmanx");
+			try!(System::new(lib_dir.unwrap_or_default()))
+		},
+		Platform::Kms => {
+			println!("Platform: KMS");
+			let mut config = KmsConfig::default();
+			if let Some(lib_dir) = lib_dir { config.lib_dir = lib_dir; }
+			try!(System::new_kms(config))
+		},
+		Platform::Surfaceless => {
+			println!("Platform: surfaceless");
+			try!(System::new_surfaceless(lib_dir.unwrap_or(LibDir::system())))
+		},
+		Platform::Mock => {
+			println!("Platform: mock (surfaceless, software rendering)");
+			try!(System::new_surfaceless(lib_dir.unwrap_or(LibDir::system())))
+		},
+	};
+
+	println!();
+	println!("Libraries:");
+	let dir = if system.lib_dir().as_os_str().is_empty() { "(search path of the dynamic linker)".to_string() } else { system.lib_dir().display().to_string() };
+	println!("  Directory: {}", dir);
+	println!("  EGL: {}", system.library_names().egl);
+	println!("  GLESv2: {}", system.library_names().glesv2);
+	if let Some(device) = system.drm_device() {
+		println!("  DRM device: {}", device.display());
+	}
+
+	println!();
+	println!("Displays:");
+	for display in &[Display::Hdmi, Display::Analog, Display::Lcd] {
+		match system.display_size(*display) {
+			Ok((width, height)) => println!("  {:?}: {}x{}", display, width, height),
+			Err(e) => println!("  {:?}: {}", display, e),
+		}
+	}
+	if let Ok(overscan) = system.firmware_overscan() {
+		println!("  Overscan: left {}, right {}, top {}, bottom {}", overscan.left, overscan.right, overscan.top, overscan.bottom);
+	}
+
+	println!();
+	let egl = try!(system.egl_info());
+	println!("EGL:");
+	println!("  Vendor: {}", egl.vendor);
+	println!("  Version: {}", egl.version);
+	println!("  Initialized version: {}.{}", system.egl_version().0, system.egl_version().1);
+	println!("  Client APIs: {}", egl.client_apis);
+	println!("  Extensions:");
+	for extension in &egl.extensions {
+		println!("    {}", extension);
+	}
+
+	println!();
+	let configs = try!(system.egl_configs());
+	println!("EGL configs ({}):", configs.len());
+	println!("  {:>4} {:>3} {:>3} {:>3} {:>3} {:>5} {:>7} {:>7} {:>7} {:>10} {:>10}", "id", "r", "g", "b", "a", "depth", "stencil", "samples", "surface", "renderable", "visual");
+	for c in &configs {
+		println!("  {:>4} {:>3} {:>3} {:>3} {:>3} {:>5} {:>7} {:>7} {:>#7x} {:>#10x} {:>#10x}", c.id, c.red, c.green, c.blue, c.alpha, c.depth, c.stencil, c.samples, c.surface_type, c.renderable_type, c.native_visual_id);
+	}
+
+	println!();
+	let window = try!(Window::new_offscreen(&system, &Default::default(), None::<&Window<&System>>));
+	// An offscreen window is not made current when it is created.
+	unsafe { window.make_current(); }
+	let gl = try!(window.gl_info());
+	println!("GL:");
+	println!("  Vendor: {}", gl.vendor);
+	println!("  Renderer: {}", gl.renderer);
+	println!("  Version: {}", gl.version);
+	println!("  Shading language version: {}", gl.shading_language_version);
+	println!("  Extensions:");
+	for extension in &gl.extensions {
+		println!("    {}", extension);
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+	use super::{run, Platform};
+
+	// Needs Mesa with surfaceless EGL and its software renderer.
+	#[test]
+	#[ignore]
+	fn run_on_mock_platform() {
+		env::set_var("LIBGL_ALWAYS_SOFTWARE", "1");
+		match run(Platform::Mock, None) {
+			Ok(()) => {},
+			Err(e) => panic!("{}", e),
+		}
+	}
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use glium;
+use glium::glutin::{Api, GlRequest};
+
+use error::Error;
+use config::{Display, DepthBits, GlesVersion, GlesVersionRange, WindowConfig};
+use facade::{FacadeOptions, PiDisplay};
+use super::System;
+
+/// Builder for a `PiDisplay` with the methods of the `WindowBuilder` of glutin.
+///
+/// Methods without a meaning on the raspberry pi (e.g. `with_title`) are accepted and ignored.
+pub struct PiWindowBuilder {
+	/// The system to create the window with. If none, `System::autodetect` is used.
+	system: Option<Arc<System>>,
+	/// The configuration of the window.
+	config: WindowConfig,
+	/// Options of the facade.
+	options: FacadeOptions,
+}
+impl PiWindowBuilder {
+	/// Create a builder with the default configuration.
+	pub fn new() -> Self {
+		PiWindowBuilder {
+			system: None,
+			config: Default::default(),
+			options: Default::default(),
+		}
+	}
+	/// Use an existing system instead of detecting one.
+	pub fn with_system(mut self, system: Arc<System>) -> Self {
+		self.system = Some(system);
+		self
+	}
+	/// Use the configuration given. Replaces everything set before.
+	pub fn with_config(mut self, config: WindowConfig) -> Self {
+		self.config = config;
+		self
+	}
+	/// Use the facade options given.
+	pub fn with_facade_options(mut self, options: FacadeOptions) -> Self {
+		self.options = options;
+		self
+	}
+	/// The size of the surface. The surface is scaled to the whole display.
+	pub fn with_dimensions(mut self, width: u32, height: u32) -> Self {
+		self.config.surface_size = Some((width, height));
+		self
+	}
+	/// The display to show the window on.
+	pub fn with_display(mut self, display: Display) -> Self {
+		self.config.display = display;
+		self
+	}
+	/// Ignored, windows have no title.
+	pub fn with_title(self, _title: String) -> Self {
+		self
+	}
+	/// Number of bits of the depth buffer. 0 disables the depth buffer.
+	pub fn with_depth_buffer(mut self, bits: u8) -> Self {
+		self.config.depth = if bits == 0 { None } else { Some(DepthBits(bits as u32)) };
+		self
+	}
+	/// Number of samples per pixel. Must be a power of two.
+	pub fn with_multisampling(mut self, samples: u16) -> Self {
+		assert!(samples.is_power_of_two());
+		self.config.samples = samples as u32;
+		self
+	}
+	/// Synchronize the swapping of the buffers with the display.
+	pub fn with_vsync(mut self) -> Self {
+		self.config.swap_interval = Some(1);
+		self
+	}
+	/// The version of OpenGL ES to use. Requests for desktop OpenGL only are ignored.
+	pub fn with_gl(mut self, request: GlRequest) -> Self {
+		match request {
+			GlRequest::Latest => {
+				self.config.gles_version = GlesVersionRange {
+					min: GlesVersion(2, 0),
+					max: GlesVersion::known()[0],
+				};
+			},
+			GlRequest::Specific(Api::OpenGlEs, (major, minor)) | GlRequest::GlThenGles { opengles_version: (major, minor), .. } => {
+				self.config.gles_version = GlesVersionRange {
+					min: GlesVersion(major, minor),
+					max: GlesVersion(major, minor),
+				};
+			},
+			GlRequest::Specific(..) => {},
+		}
+		self
+	}
+	/// Request a debug context.
+	pub fn with_gl_debug_flag(mut self, flag: bool) -> Self {
+		self.config.debug = flag;
+		self
+	}
+	/// Create the window and its display.
+	pub fn build_glium(self) -> Result<PiDisplay, glium::GliumCreationError<Error>> {
+		let system = match self.system {
+			Some(system) => system,
+			None => {
+				let (system, _) = try!(System::autodetect().map_err(|e| { glium::GliumCreationError::BackendCreationError(e) }));
+				Arc::new(system)
+			},
+		};
+		PiDisplay::with_options(&system, &self.config, self.options)
+	}
+	/// Create the window and its display with the given handling of debug messages.
+	pub fn build_glium_debug(mut self, debug: glium::debug::DebugCallbackBehavior) -> Result<PiDisplay, glium::GliumCreationError<Error>> {
+		self.options.debug = debug;
+		self.build_glium()
+	}
+}
+impl Default for PiWindowBuilder {
+	fn default() -> Self {
+		PiWindowBuilder::new()
+	}
+}
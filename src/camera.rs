@@ -0,0 +1,281 @@
+use std::collections::VecDeque;
+use std::mem;
+use std::ptr;
+use std::rc::Rc;
+use std::sync::Mutex;
+
+use libc;
+use glium::backend::Facade;
+use shared_library::dynamic_library::DynamicLibrary;
+
+use ffi::mmal;
+use error::Error;
+use frame::{FrameSource, VideoFrame};
+use image::{ImageSource, ExternalTexture};
+use super::System;
+
+/// Configuration of the camera.
+#[derive(Clone, Copy, Debug)]
+pub struct CameraConfig {
+	/// Index of the camera. Defaults to 0.
+	pub camera_num: u32,
+	/// Size of the frames. Defaults to 1280x720.
+	pub dimensions: (u32, u32),
+	/// Frames per second. Defaults to 30.
+	pub frame_rate: u32,
+}
+impl Default for CameraConfig {
+	fn default() -> Self {
+		CameraConfig {
+			camera_num: 0,
+			dimensions: (1280, 720),
+			frame_rate: 30,
+		}
+	}
+}
+
+/// State shared with the callbacks, which run on the MMAL thread. Referenced by the userdata of the ports.
+struct Received {
+	/// Buffers returned by the preview port.
+	buffers: Mutex<VecDeque<*mut mmal::MmalBufferHeader>>,
+	/// mmal_buffer_header_release
+	release: unsafe extern "C" fn(*mut mmal::MmalBufferHeader),
+}
+
+/// The MMAL camera component. Shared by the camera and its frames.
+struct Component {
+	/// MMAL core library.
+	lib_mmal_core: mmal::LibMmalCore,
+	/// Registers the VideoCore components when loaded. Kept loaded as long as the component exists.
+	_dlib_mmal_vc_client: DynamicLibrary,
+	/// The camera component.
+	component: *mut mmal::MmalComponent,
+	/// The preview port the frames are read from.
+	port: *mut mmal::MmalPort,
+	/// Buffers of the preview port.
+	pool: *mut mmal::MmalPool,
+	/// State shared with the callbacks.
+	received: Box<Received>,
+	/// The size of the frames.
+	dimensions: (u32, u32),
+}
+impl Component {
+	/// Release a buffer and send all free buffers to the camera again.
+	unsafe fn recycle(&self, buffer: *mut mmal::MmalBufferHeader) {
+		(self.lib_mmal_core.mmal_buffer_header_release)(buffer);
+		self.send_free_buffers();
+	}
+	/// Send the free buffers of the pool to the camera.
+	unsafe fn send_free_buffers(&self) {
+		if self.port.is_null() || (*self.port).is_enabled == 0 { return; }
+		loop {
+			let buffer = (self.lib_mmal_core.mmal_queue_get)((*self.pool).queue);
+			if buffer.is_null() { break; }
+			if (self.lib_mmal_core.mmal_port_send_buffer)(self.port, buffer) != mmal::MMAL_SUCCESS {
+				(self.lib_mmal_core.mmal_buffer_header_release)(buffer);
+				break;
+			}
+		}
+	}
+}
+impl Drop for Component {
+	fn drop(&mut self) {
+		unsafe {
+			if !self.component.is_null() {
+				(self.lib_mmal_core.mmal_component_disable)(self.component);
+			}
+			if !self.port.is_null() && (*self.port).is_enabled != 0 {
+				(self.lib_mmal_core.mmal_port_disable)(self.port);
+			}
+			for buffer in self.received.buffers.lock().unwrap().drain(..) {
+				(self.lib_mmal_core.mmal_buffer_header_release)(buffer);
+			}
+			if !self.pool.is_null() {
+				(self.lib_mmal_core.mmal_port_pool_destroy)(self.port, self.pool);
+			}
+			if !self.component.is_null() {
+				let control = (*self.component).control;
+				if (*control).is_enabled != 0 { (self.lib_mmal_core.mmal_port_disable)(control); }
+				(self.lib_mmal_core.mmal_component_destroy)(self.component);
+			}
+		}
+	}
+}
+
+/// The raspberry pi camera, read through MMAL. Only supported on the dispmanx platform.
+///
+/// Frames are opaque buffers of the VPU which are shown by external textures without copying them.
+pub struct Camera {
+	component: Rc<Component>,
+}
+impl Camera {
+	/// Open and start the camera. The MMAL libraries are loaded from the library directory of the system.
+	pub fn new(system: &System, config: &CameraConfig) -> Result<Self, Error> {
+		try!(system.lib_bcm_host());
+		let lib_dir = system.lib_dir();
+		let dlib_mmal_vc_client = try!(DynamicLibrary::open(Some(&lib_dir.join("libmmal_vc_client.so"))).map_err(|e| { Error::Dl(e) }));
+		let lib_mmal_core = try!(mmal::LibMmalCore::open(&lib_dir.join("libmmal_core.so")).map_err(|e| { Error::Sl(e) }));
+		let received = Box::new(Received {
+			buffers: Mutex::new(VecDeque::new()),
+			release: lib_mmal_core.mmal_buffer_header_release,
+		});
+		let mut component = Component {
+			lib_mmal_core: lib_mmal_core,
+			_dlib_mmal_vc_client: dlib_mmal_vc_client,
+			component: ptr::null_mut(),
+			port: ptr::null_mut(),
+			pool: ptr::null_mut(),
+			received: received,
+			dimensions: config.dimensions,
+		};
+		try!(unsafe { component.open(config) });
+		Ok(Camera { component: Rc::new(component) })
+	}
+}
+impl Component {
+	/// Create, configure and enable the camera component. Destroyed by drop on failure.
+	unsafe fn open(&mut self, config: &CameraConfig) -> Result<(), Error> {
+		let lib = &self.lib_mmal_core;
+		if (lib.mmal_component_create)(mmal::MMAL_COMPONENT_DEFAULT_CAMERA.as_ptr() as *const libc::c_char, &mut self.component as *mut *mut mmal::MmalComponent) != mmal::MMAL_SUCCESS {
+			self.component = ptr::null_mut();
+			return Err(Error::Fn("mmal_component_create"));
+		}
+		let control = (*self.component).control;
+		(*control).userdata = &*self.received as *const Received as *mut libc::c_void;
+
+		let camera_num = mmal::MmalParameterInt32 {
+			hdr: mmal::MmalParameterHeader { id: mmal::MMAL_PARAMETER_CAMERA_NUM, size: mem::size_of::<mmal::MmalParameterInt32>() as libc::uint32_t },
+			value: config.camera_num as libc::int32_t,
+		};
+		if (lib.mmal_port_parameter_set)(control, &camera_num.hdr as *const mmal::MmalParameterHeader) != mmal::MMAL_SUCCESS { return Err(Error::Fn("mmal_port_parameter_set(MMAL_PARAMETER_CAMERA_NUM)")); }
+		if (*self.component).output_num == 0 { return Err(Error::Unsupported("the camera has no output ports")); }
+		if (lib.mmal_port_enable)(control, control_callback) != mmal::MMAL_SUCCESS { return Err(Error::Fn("mmal_port_enable(control)")); }
+
+		let (width, height) = config.dimensions;
+		let camera_config = mmal::MmalParameterCameraConfig {
+			hdr: mmal::MmalParameterHeader { id: mmal::MMAL_PARAMETER_CAMERA_CONFIG, size: mem::size_of::<mmal::MmalParameterCameraConfig>() as libc::uint32_t },
+			max_stills_w: width,
+			max_stills_h: height,
+			stills_yuv422: 0,
+			one_shot_stills: 0,
+			max_preview_video_w: width,
+			max_preview_video_h: height,
+			num_preview_video_frames: 3,
+			stills_capture_circular_buffer_height: 0,
+			fast_preview_resume: 0,
+			use_stc_timestamp: mmal::MMAL_PARAM_TIMESTAMP_MODE_RESET_STC,
+		};
+		if (lib.mmal_port_parameter_set)(control, &camera_config.hdr as *const mmal::MmalParameterHeader) != mmal::MMAL_SUCCESS { return Err(Error::Fn("mmal_port_parameter_set(MMAL_PARAMETER_CAMERA_CONFIG)")); }
+
+		// The preview port delivers opaque buffers which can be imported by EGL.
+		self.port = *(*self.component).output;
+		let format = (*self.port).format;
+		(*format).encoding = mmal::MMAL_ENCODING_OPAQUE;
+		(*format).encoding_variant = 0;
+		let video = (*format).es;
+		(*video).width = (width + 31) & !31;
+		(*video).height = (height + 15) & !15;
+		(*video).crop = mmal::MmalRect { x: 0, y: 0, width: width as libc::int32_t, height: height as libc::int32_t };
+		(*video).frame_rate = mmal::MmalRational { num: config.frame_rate as libc::int32_t, den: 1 };
+		if (lib.mmal_port_format_commit)(self.port) != mmal::MMAL_SUCCESS { return Err(Error::Fn("mmal_port_format_commit")); }
+
+		let zero_copy = mmal::MmalParameterInt32 {
+			hdr: mmal::MmalParameterHeader { id: mmal::MMAL_PARAMETER_ZERO_COPY, size: mem::size_of::<mmal::MmalParameterInt32>() as libc::uint32_t },
+			value: 1,
+		};
+		if (lib.mmal_port_parameter_set)(self.port, &zero_copy.hdr as *const mmal::MmalParameterHeader) != mmal::MMAL_SUCCESS { return Err(Error::Fn("mmal_port_parameter_set(MMAL_PARAMETER_ZERO_COPY)")); }
+
+		(*self.port).buffer_num = if (*self.port).buffer_num_recommended < 3 { 3 } else { (*self.port).buffer_num_recommended };
+		(*self.port).buffer_size = (*self.port).buffer_size_recommended;
+		self.pool = (lib.mmal_port_pool_create)(self.port, (*self.port).buffer_num, (*self.port).buffer_size);
+		if self.pool.is_null() { return Err(Error::Fn("mmal_port_pool_create")); }
+
+		(*self.port).userdata = &*self.received as *const Received as *mut libc::c_void;
+		if (lib.mmal_port_enable)(self.port, output_callback) != mmal::MMAL_SUCCESS { return Err(Error::Fn("mmal_port_enable")); }
+		if (lib.mmal_component_enable)(self.component) != mmal::MMAL_SUCCESS { return Err(Error::Fn("mmal_component_enable")); }
+		self.send_free_buffers();
+		Ok(())
+	}
+}
+impl FrameSource for Camera {
+	type Frame = CameraFrame;
+	fn dimensions(&self) -> (u32, u32) {
+		self.component.dimensions
+	}
+	fn next_frame(&mut self) -> Result<Option<CameraFrame>, Error> {
+		let buffers: Vec<_> = self.component.received.buffers.lock().unwrap().drain(..).collect();
+		let mut latest = None;
+		for buffer in buffers {
+			match latest {
+				Some(older) => unsafe { self.component.recycle(older) },
+				None => {},
+			}
+			latest = Some(buffer);
+		}
+		Ok(latest.map(|buffer| {
+			CameraFrame {
+				component: self.component.clone(),
+				buffer: buffer,
+			}
+		}))
+	}
+}
+
+/// A frame of the camera. Dropping it sends its buffer back to the camera.
+pub struct CameraFrame {
+	/// The camera.
+	component: Rc<Component>,
+	/// The MMAL buffer holding the opaque image.
+	buffer: *mut mmal::MmalBufferHeader,
+}
+impl CameraFrame {
+	/// Presentation time stamp in microseconds.
+	pub fn pts(&self) -> i64 {
+		unsafe { (*self.buffer).pts }
+	}
+	/// The frame as source of an EGL image.
+	pub fn image_source(&self) -> ImageSource {
+		let (width, height) = self.component.dimensions;
+		ImageSource::Multimedia {
+			buffer: unsafe { (*self.buffer).data } as *mut libc::c_void,
+			width: width,
+			height: height,
+		}
+	}
+	/// Show the frame by a new external texture of the context of the facade. The frame is borrowed as long as the texture exists.
+	pub fn external_texture<'a, F>(&'a self, system: &'a System, facade: &F) -> Result<ExternalTexture<'a>, Error> where F: Facade {
+		let image = try!(system.create_image(&self.image_source()));
+		ExternalTexture::new(facade, image)
+	}
+}
+impl<'a> VideoFrame<'a> for CameraFrame {
+	type Texture = ExternalTexture<'a>;
+	fn pts(&self) -> i64 {
+		CameraFrame::pts(self)
+	}
+	/// An external texture, see `external_texture`.
+	fn texture<F>(&'a self, system: &'a System, facade: &F) -> Result<ExternalTexture<'a>, Error> where F: Facade {
+		self.external_texture(system, facade)
+	}
+}
+impl Drop for CameraFrame {
+	fn drop(&mut self) {
+		unsafe { self.component.recycle(self.buffer); }
+	}
+}
+
+// Called by MMAL with events of the control port. They are not used, the buffers only have to be released.
+extern "C" fn control_callback(port: *mut mmal::MmalPort, buffer: *mut mmal::MmalBufferHeader) {
+	unsafe {
+		let received = (*port).userdata as *const Received;
+		if !received.is_null() { ((*received).release)(buffer); }
+	}
+}
+
+// Called by MMAL on its own thread with every filled buffer of the preview port.
+extern "C" fn output_callback(port: *mut mmal::MmalPort, buffer: *mut mmal::MmalBufferHeader) {
+	unsafe {
+		let received = (*port).userdata as *const Received;
+		if !received.is_null() { (*received).buffers.lock().unwrap().push_back(buffer); }
+	}
+}
@@ -1,15 +1,25 @@
+use std::cmp;
+use std::fs::File;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 
 use libc;
 
 /// Wrapper for the directory where the libraries are stored. Defaults to /opt/vc/lib
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct LibDir(pub PathBuf);
 impl Default for LibDir {
 	fn default() -> Self {
 		LibDir(Path::new("/opt/vc/lib").to_path_buf())
 	}
 }
+impl LibDir {
+	/// Use the search path of the dynamic linker instead of a fixed directory.
+	pub fn system() -> Self {
+		LibDir(PathBuf::new())
+	}
+}
 impl Deref for LibDir {
 	type Target = Path;
 	fn deref(&self) -> &Self::Target {
@@ -17,8 +27,80 @@ impl Deref for LibDir {
 	}
 }
 
+/// File names of the libraries loaded from the library directory. Defaults to the legacy Broadcom names.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LibraryNames {
+	/// Bcm-Host library. Only used by dispmanx.
+	pub bcm_host: String,
+	/// EGL library.
+	pub egl: String,
+	/// GLES library.
+	pub glesv2: String,
+}
+impl LibraryNames {
+	/// Names of the legacy Broadcom libraries (libEGL.so, libGLESv2.so).
+	pub fn broadcom() -> Self {
+		LibraryNames {
+			bcm_host: "libbcm_host.so".to_string(),
+			egl: "libEGL.so".to_string(),
+			glesv2: "libGLESv2.so".to_string(),
+		}
+	}
+	/// Names of the Broadcom libraries of newer firmware packages (libbrcmEGL.so, libbrcmGLESv2.so).
+	pub fn broadcom_renamed() -> Self {
+		LibraryNames {
+			bcm_host: "libbcm_host.so".to_string(),
+			egl: "libbrcmEGL.so".to_string(),
+			glesv2: "libbrcmGLESv2.so".to_string(),
+		}
+	}
+	/// Names of the Mesa libraries (libEGL.so.1, libGLESv2.so.2).
+	pub fn mesa() -> Self {
+		LibraryNames {
+			bcm_host: "libbcm_host.so".to_string(),
+			egl: "libEGL.so.1".to_string(),
+			glesv2: "libGLESv2.so.2".to_string(),
+		}
+	}
+	/// The names tried by `System::new`: both Broadcom naming schemes.
+	pub fn search_list() -> Vec<Self> {
+		vec![LibraryNames::broadcom(), LibraryNames::broadcom_renamed()]
+	}
+}
+impl Default for LibraryNames {
+	fn default() -> Self {
+		LibraryNames::broadcom()
+	}
+}
+
+/// Configuration of the KMS/DRM backend.
+pub struct KmsConfig {
+	/// The directory of libEGL, libGLESv2, libgbm and libdrm. Defaults to the search path of the dynamic linker.
+	pub lib_dir: LibDir,
+	/// The DRM device to use. If none, the first device under /dev/dri which supports mode setting is used.
+	pub device: Option<PathBuf>,
+	/// Name of the connector to use for every display (e.g. "HDMI-A-1" or "Virtual-1").
+	/// If none, the first connected connector matching the display is used.
+	pub connector: Option<String>,
+	/// An already opened DRM device to use instead of opening one, e.g. a vkms device or a mock for testing.
+	/// `device` only names it then.
+	pub file: Option<File>,
+}
+impl Default for KmsConfig {
+	fn default() -> Self {
+		KmsConfig {
+			lib_dir: LibDir::system(),
+			device: None,
+			connector: None,
+			file: None,
+		}
+	}
+}
+
 /// Possible displays. Defaults to Hdmi.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "snake_case"))]
 pub enum Display {
 	Hdmi,
 	Analog,
@@ -39,6 +121,7 @@ impl Default for Display {
 
 /// Wrapper for color bits. Defaults to 8.
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct ColorBits(pub u32);
 impl Default for ColorBits {
 	fn default() -> Self {
@@ -54,6 +137,7 @@ impl Deref for ColorBits {
 
 /// Wrapper for depth bits. Defaults to 16.
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct DepthBits(pub u32);
 impl Default for DepthBits {
 	fn default() -> Self {
@@ -67,8 +151,261 @@ impl Deref for DepthBits {
 	}
 }
 
+/// OpenGL ES version (major, minor).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct GlesVersion(pub u8, pub u8);
+impl GlesVersion {
+	/// The versions which can be requested, from the highest to the lowest.
+	pub fn known() -> [GlesVersion; 4] {
+		[GlesVersion(3, 2), GlesVersion(3, 1), GlesVersion(3, 0), GlesVersion(2, 0)]
+	}
+	/// Parse the version of a GL_VERSION string (e.g. "OpenGL ES 3.1 Mesa 19.3.2").
+	pub fn parse(version: &str) -> Option<Self> {
+		let prefix = "OpenGL ES ";
+		if !version.starts_with(prefix) { return None; }
+		let mut numbers = version[prefix.len()..].split(|c: char| { !c.is_digit(10) });
+		match (numbers.next().and_then(|n| { n.parse().ok() }), numbers.next().and_then(|n| { n.parse().ok() })) {
+			(Some(major), Some(minor)) => Some(GlesVersion(major, minor)),
+			_ => None,
+		}
+	}
+}
+
+/// Range of acceptable OpenGL ES versions. The highest version in the range is tried first. Defaults to 2.0 only.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct GlesVersionRange {
+	/// The lowest acceptable version.
+	pub min: GlesVersion,
+	/// The highest version to try.
+	pub max: GlesVersion,
+}
+impl GlesVersionRange {
+	/// The known versions within the range, from the highest to the lowest.
+	pub fn versions(&self) -> Vec<GlesVersion> {
+		GlesVersion::known().iter().cloned().filter(|v| { *v >= self.min && *v <= self.max }).collect()
+	}
+}
+impl Default for GlesVersionRange {
+	fn default() -> Self {
+		GlesVersionRange {
+			min: GlesVersion(2, 0),
+			max: GlesVersion(2, 0),
+		}
+	}
+}
+
+/// Robustness of the context (EGL_EXT_create_context_robustness). Defaults to a context without robust buffer access.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "snake_case"))]
+pub enum Robustness {
+	/// No robust buffer access.
+	NotRobust,
+	/// Robust buffer access without reset notification.
+	NoResetNotification,
+	/// Robust buffer access. A graphics reset loses the context and is reported by glGetGraphicsResetStatus.
+	LoseContextOnReset,
+}
+impl Default for Robustness {
+	fn default() -> Self { Robustness::NotRobust }
+}
+
+/// A rectangle in pixels with the origin at the top left.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Rect {
+	pub x: i32,
+	pub y: i32,
+	pub width: u32,
+	pub height: u32,
+}
+impl Rect {
+	/// A rectangle of a position and size.
+	pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+		Rect {
+			x: x,
+			y: y,
+			width: width,
+			height: height,
+		}
+	}
+	/// A rectangle of a size at the origin.
+	pub fn from_size(size: (u32, u32)) -> Self {
+		Rect::new(0, 0, size.0, size.1)
+	}
+	/// The position of the top left corner.
+	pub fn position(&self) -> (i32, i32) {
+		(self.x, self.y)
+	}
+	/// The size.
+	pub fn size(&self) -> (u32, u32) {
+		(self.width, self.height)
+	}
+}
+
+/// A rectangle in 16.16 fixed point pixels with the origin at the top left.
+/// Dispmanx crops the source of elements with sub-pixel precision, e.g. to zoom and pan smoothly.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct FixedRect {
+	pub x: i32,
+	pub y: i32,
+	pub width: u32,
+	pub height: u32,
+}
+impl FixedRect {
+	/// A rectangle of a position and size in pixels, rounded to 1/65536 pixel.
+	pub fn from_f32(x: f32, y: f32, width: f32, height: f32) -> Self {
+		FixedRect {
+			x: (x * 65536.0).round() as i32,
+			y: (y * 65536.0).round() as i32,
+			width: (width * 65536.0).round() as u32,
+			height: (height * 65536.0).round() as u32,
+		}
+	}
+	/// The position and size (x, y, width, height) in pixels.
+	pub fn to_f32(&self) -> (f32, f32, f32, f32) {
+		(self.x as f32 / 65536.0, self.y as f32 / 65536.0, self.width as f32 / 65536.0, self.height as f32 / 65536.0)
+	}
+}
+impl From<Rect> for FixedRect {
+	fn from(rect: Rect) -> Self {
+		FixedRect {
+			x: rect.x << 16,
+			y: rect.y << 16,
+			width: rect.width << 16,
+			height: rect.height << 16,
+		}
+	}
+}
+
+/// Border of a display which is hidden by overscan, in pixels of the display.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Overscan {
+	pub left: u32,
+	pub right: u32,
+	pub top: u32,
+	pub bottom: u32,
+}
+impl Overscan {
+	/// The part of a display of a size which is visible.
+	pub fn safe_area(&self, display_size: (u32, u32)) -> Rect {
+		let width = display_size.0.saturating_sub(self.left + self.right);
+		let height = display_size.1.saturating_sub(self.top + self.bottom);
+		Rect::new(self.left.min(display_size.0) as i32, self.top.min(display_size.1) as i32, width, height)
+	}
+}
+
+/// How a surface whose size differs from the display is scaled. Defaults to stretching it over the whole display.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "snake_case"))]
+pub enum Scaling {
+	/// Stretch the surface over the whole display. Distorts the aspect ratio.
+	Stretch,
+	/// Scale the surface to fit into the display keeping the aspect ratio. Leaves bars at the sides or at the top and bottom.
+	Fit,
+	/// Scale the surface to fill the display keeping the aspect ratio. Crops the sides or the top and bottom of the surface.
+	Fill,
+	/// Scale the surface by the largest integer factor which fits, e.g. for pixel art. Centered, crops if the surface is larger than the display.
+	Integer,
+	/// Show the surface unscaled in the center of the display. Crops if the surface is larger than the display.
+	Center,
+}
+impl Default for Scaling {
+	fn default() -> Self { Scaling::Stretch }
+}
+impl Scaling {
+	/// Compute the part of the source shown and where it is shown on the display. The source is usually the whole surface.
+	pub fn mapping(&self, source: FixedRect, display_size: (u32, u32)) -> SurfaceMapping {
+		let (sw, sh) = (source.width as f64 / 65536.0, source.height as f64 / 65536.0);
+		let (dw, dh) = (display_size.0 as f64, display_size.1 as f64);
+		let (scale_x, scale_y) = match *self {
+			Scaling::Stretch => (dw / sw, dh / sh),
+			Scaling::Fit => { let s = (dw / sw).min(dh / sh); (s, s) },
+			Scaling::Fill => { let s = (dw / sw).max(dh / sh); (s, s) },
+			Scaling::Integer => { let s = (dw / sw).min(dh / sh).floor().max(1.0); (s, s) },
+			Scaling::Center => (1.0, 1.0),
+		};
+		let (source_x, source_width, destination_x, destination_width) = map_axis(source.x, source.width, display_size.0, scale_x);
+		let (source_y, source_height, destination_y, destination_height) = map_axis(source.y, source.height, display_size.1, scale_y);
+		SurfaceMapping {
+			source: FixedRect { x: source_x, y: source_y, width: source_width, height: source_height },
+			destination: Rect::new(destination_x, destination_y, destination_width, destination_height),
+		}
+	}
+}
+
+// Map one axis of the source (16.16 fixed point) to the display with a scale, centered and cropped to the display.
+// Returns the offset and length of the source (16.16 fixed point) and of the destination (pixels).
+fn map_axis(source_offset: i32, source_length: u32, display: u32, scale: f64) -> (i32, u32, i32, u32) {
+	let visible = (source_length as f64).min(display as f64 * 65536.0 / scale);
+	let visible = (visible.round() as u32).max(1).min(source_length);
+	let destination_length = ((visible as f64 / 65536.0 * scale).round() as u32).min(display);
+	(source_offset + ((source_length - visible) / 2) as i32, visible, ((display - destination_length) / 2) as i32, destination_length)
+}
+
+/// The mapping between the surface of a window and the display. The origin of both is at the top left.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SurfaceMapping {
+	/// The part of the surface which is shown.
+	pub source: FixedRect,
+	/// Where the part of the surface is shown on the display.
+	pub destination: Rect,
+}
+impl SurfaceMapping {
+	/// The mapping of a surface shown unscaled, e.g. on KMS.
+	pub fn identity(size: (u32, u32)) -> Self {
+		SurfaceMapping {
+			source: FixedRect::from(Rect::from_size(size)),
+			destination: Rect::from_size(size),
+		}
+	}
+	/// The part of a surface of a size which is shown inside an area of the display, e.g. the safe area inside the overscan.
+	/// Rounded inwards, so that the whole part is visible. Empty if nothing of the surface is shown inside the area.
+	pub fn surface_area(&self, area: Rect, surface_size: (u32, u32)) -> Rect {
+		let (width, height) = surface_size;
+		// The part of the destination inside the area.
+		let d = self.destination;
+		let left = cmp::max(d.x, area.x) as f64;
+		let top = cmp::max(d.y, area.y) as f64;
+		let right = cmp::min(d.x + d.width as i32, area.x + area.width as i32) as f64;
+		let bottom = cmp::min(d.y + d.height as i32, area.y + area.height as i32) as f64;
+		if right <= left || bottom <= top || d.width == 0 || d.height == 0 { return Rect::default(); }
+		// Map it to the surface, rounding inwards.
+		let (sx, sy, sw, sh) = self.source.to_f32();
+		let (sx, sy, sw, sh) = (sx as f64, sy as f64, sw as f64, sh as f64);
+		let to_x = |x: f64| { (sx + (x - d.x as f64) * sw / d.width as f64).max(0.0).min(width as f64) };
+		let to_y = |y: f64| { (sy + (y - d.y as f64) * sh / d.height as f64).max(0.0).min(height as f64) };
+		let (left, right) = (to_x(left).ceil() as u32, to_x(right).floor() as u32);
+		let (top, bottom) = (to_y(top).ceil() as u32, to_y(bottom).floor() as u32);
+		Rect::new(left as i32, top as i32, right.saturating_sub(left), bottom.saturating_sub(top))
+	}
+	/// Convert a position on the display, e.g. of a touch, into a position on the surface. None if the position is outside of the surface shown.
+	pub fn display_to_surface(&self, position: (f32, f32)) -> Option<(f32, f32)> {
+		let (sx, sy, sw, sh) = self.source.to_f32();
+		let d = self.destination;
+		let (x, y) = (position.0 - d.x as f32, position.1 - d.y as f32);
+		if x < 0.0 || y < 0.0 || x >= d.width as f32 || y >= d.height as f32 { return None; }
+		Some((sx + x * sw / d.width as f32, sy + y * sh / d.height as f32))
+	}
+	/// Convert a position on the surface into a position on the display. None if the position is cropped.
+	pub fn surface_to_display(&self, position: (f32, f32)) -> Option<(f32, f32)> {
+		let (sx, sy, sw, sh) = self.source.to_f32();
+		let d = self.destination;
+		let (x, y) = (position.0 - sx, position.1 - sy);
+		if x < 0.0 || y < 0.0 || x >= sw || y >= sh { return None; }
+		Some((d.x as f32 + x * d.width as f32 / sw, d.y as f32 + y * d.height as f32 / sh))
+	}
+}
+
 /// Window configuration.
 #[derive(Copy, Clone, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", serde(default))]
 pub struct WindowConfig {
 	/// The display to use.
 	pub display: Display,
@@ -84,6 +421,104 @@ pub struct WindowConfig {
 	pub alpha: Option<ColorBits>,
 	/// Number of bits per pixel used for the depth buffer.
 	pub depth: Option<DepthBits>,
+	/// Number of samples per pixel used for multisampling. 0 disables multisampling.
+	pub samples: u32,
+	/// Minimum number of video frames per swap (0 disables vsync). If none, the default of the driver is used.
+	pub swap_interval: Option<u32>,
+	/// Whether to request a debug context. Creation fails if the driver supports neither EGL 1.5 nor EGL_KHR_create_context.
+	pub debug: bool,
+	/// The robustness of the context. Creation fails if the driver does not support the requested robustness.
+	pub robustness: Robustness,
+	/// The dispmanx layer of the window. Elements of higher layers are shown above. Defaults to 0.
+	pub layer: i32,
+	/// How the surface is scaled if its size differs from the display. Only used by dispmanx.
+	pub scaling: Scaling,
+	/// Color (red, green, blue) of a dispmanx element beneath the window which fills the bars left by the scaling. If none, the layers below show through.
+	pub background: Option<(u8, u8, u8)>,
+	/// Whether the surface is scaled into the part of the display inside the overscan, so that it is completely visible.
+	/// Meant for displays without overscan compensation by the firmware (disable_overscan=1), with the overscan given by `overscan`.
+	pub inset_overscan: bool,
+	/// The OpenGL ES versions to try.
+	pub gles_version: GlesVersionRange,
+	/// The part of the surface shown, in 16.16 fixed point pixels. If none, the whole surface. Moving it pans, shrinking it zooms.
+	pub source: Option<FixedRect>,
+	/// The overscan of the display. If none, `System::firmware_overscan` is used on dispmanx (zero if it fails) and zero otherwise.
+	pub overscan: Option<Overscan>,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_gles_version() {
+		assert_eq!(GlesVersion::parse("OpenGL ES 2.0"), Some(GlesVersion(2, 0)));
+		assert_eq!(GlesVersion::parse("OpenGL ES 3.1 Mesa 19.3.2"), Some(GlesVersion(3, 1)));
+		assert_eq!(GlesVersion::parse("OpenGL ES 3.2 V3D 4.2"), Some(GlesVersion(3, 2)));
+	}
+
+	#[test]
+	fn parse_invalid_gles_version() {
+		assert_eq!(GlesVersion::parse(""), None);
+		assert_eq!(GlesVersion::parse("OpenGL ES "), None);
+		assert_eq!(GlesVersion::parse("OpenGL ES 3"), None);
+		assert_eq!(GlesVersion::parse("OpenGL ES x.1"), None);
+		assert_eq!(GlesVersion::parse("OpenGL ES 300.0"), None);
+		assert_eq!(GlesVersion::parse("4.6.0 NVIDIA 440.82"), None);
+		assert_eq!(GlesVersion::parse("OpenGL ES-CM 1.1"), None);
+	}
+
+	#[test]
+	fn gles_version_range() {
+		assert_eq!(GlesVersionRange::default().versions(), vec![GlesVersion(2, 0)]);
+		let range = GlesVersionRange { min: GlesVersion(2, 0), max: GlesVersion(3, 1) };
+		assert_eq!(range.versions(), vec![GlesVersion(3, 1), GlesVersion(3, 0), GlesVersion(2, 0)]);
+		let range = GlesVersionRange { min: GlesVersion(3, 0), max: GlesVersion(9, 9) };
+		assert_eq!(range.versions(), vec![GlesVersion(3, 2), GlesVersion(3, 1), GlesVersion(3, 0)]);
+		let range = GlesVersionRange { min: GlesVersion(3, 1), max: GlesVersion(3, 0) };
+		assert_eq!(range.versions(), vec![]);
+	}
+
+	#[test]
+	fn overscan_safe_area() {
+		assert_eq!(Overscan::default().safe_area((1920, 1080)), Rect::new(0, 0, 1920, 1080));
+		let overscan = Overscan { left: 32, right: 16, top: 8, bottom: 24 };
+		assert_eq!(overscan.safe_area((1920, 1080)), Rect::new(32, 8, 1872, 1048));
+		let overscan = Overscan { left: 2000, right: 0, top: 600, bottom: 600 };
+		assert_eq!(overscan.safe_area((1920, 1080)), Rect::new(1920, 600, 0, 0));
+	}
+
+	#[test]
+	fn scaling_mapping() {
+		let source = FixedRect::from(Rect::from_size((320, 240)));
+		let mapping = Scaling::Stretch.mapping(source, (1920, 1080));
+		assert_eq!((mapping.source, mapping.destination), (source, Rect::new(0, 0, 1920, 1080)));
+		let mapping = Scaling::Fit.mapping(source, (1920, 1080));
+		assert_eq!((mapping.source, mapping.destination), (source, Rect::new(240, 0, 1440, 1080)));
+		let mapping = Scaling::Fill.mapping(source, (1920, 1080));
+		assert_eq!((mapping.source, mapping.destination), (FixedRect::from(Rect::new(0, 30, 320, 180)), Rect::new(0, 0, 1920, 1080)));
+		let mapping = Scaling::Integer.mapping(source, (1920, 1080));
+		assert_eq!((mapping.source, mapping.destination), (source, Rect::new(320, 60, 1280, 960)));
+		let mapping = Scaling::Center.mapping(FixedRect::from(Rect::from_size((2000, 100))), (1920, 1080));
+		assert_eq!((mapping.source, mapping.destination), (FixedRect::from(Rect::new(40, 0, 1920, 100)), Rect::new(0, 490, 1920, 100)));
+	}
+
+	#[test]
+	fn map_axis_crops_and_centers() {
+		assert_eq!(map_axis(0, 100 << 16, 50, 1.0), (25 << 16, 50 << 16, 0, 50));
+		assert_eq!(map_axis(10 << 16, 100 << 16, 400, 2.0), (10 << 16, 100 << 16, 100, 200));
+		assert_eq!(map_axis(0, 1, 1920, 1.0), (0, 1, 960, 0));
+	}
+
+	#[test]
+	fn surface_area() {
+		let mapping = Scaling::Fit.mapping(FixedRect::from(Rect::from_size((320, 240))), (1920, 1080));
+		assert_eq!(mapping.surface_area(Rect::new(96, 54, 1728, 972), (320, 240)), Rect::new(0, 12, 320, 216));
+		assert_eq!(mapping.surface_area(Rect::from_size((1920, 1080)), (320, 240)), Rect::from_size((320, 240)));
+		assert_eq!(mapping.surface_area(Rect::new(0, 0, 240, 1080), (320, 240)), Rect::default());
+		let identity = SurfaceMapping::identity((640, 480));
+		assert_eq!(identity.surface_area(Rect::new(10, 10, 620, 460), (640, 480)), Rect::new(10, 10, 620, 460));
+	}
 }
 
 
@@ -0,0 +1,17 @@
+use glium::debug::{DebugCallbackBehavior, Severity};
+
+/// A debug behavior which routes the messages of the driver (KHR_debug) into the `log` crate.
+/// High severity messages are logged as errors, medium as warnings, low as info and notifications as debug.
+pub fn log_debug_callback() -> DebugCallbackBehavior {
+	DebugCallbackBehavior::Custom {
+		callback: Box::new(|source, message_type, severity, id, _report_debug_output, message| {
+			match severity {
+				Severity::High => error!("{:?} {:?} {}: {}", source, message_type, id, message),
+				Severity::Medium => warn!("{:?} {:?} {}: {}", source, message_type, id, message),
+				Severity::Low => info!("{:?} {:?} {}: {}", source, message_type, id, message),
+				Severity::Notification => debug!("{:?} {:?} {}: {}", source, message_type, id, message),
+			}
+		}),
+		synchronous: false,
+	}
+}
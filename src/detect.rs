@@ -0,0 +1,58 @@
+use std::default::Default;
+
+use error::Error;
+use config::{LibDir, LibraryNames, KmsConfig};
+use super::System;
+
+/// A way of creating the system probed by `System::autodetect`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Probe {
+	/// Dispmanx with the legacy Broadcom libraries (libEGL.so, libGLESv2.so) in the default `LibDir`.
+	Broadcom,
+	/// Dispmanx with the Broadcom libraries of newer firmware packages (libbrcmEGL.so, libbrcmGLESv2.so) in the default `LibDir`.
+	BroadcomRenamed,
+	/// KMS/DRM with GBM surfaces.
+	Kms,
+	/// Mesa without any display.
+	Surfaceless,
+}
+impl Probe {
+	/// All probes in the order they are tried.
+	pub fn all() -> [Probe; 4] {
+		[Probe::Broadcom, Probe::BroadcomRenamed, Probe::Kms, Probe::Surfaceless]
+	}
+	/// Try to create a system using this probe.
+	pub fn create(&self) -> Result<System, Error> {
+		match *self {
+			Probe::Broadcom => System::with_library_names(Default::default(), &[LibraryNames::broadcom()]),
+			Probe::BroadcomRenamed => System::with_library_names(Default::default(), &[LibraryNames::broadcom_renamed()]),
+			Probe::Kms => System::new_kms(KmsConfig::default()),
+			Probe::Surfaceless => System::new_surfaceless(LibDir::system()),
+		}
+	}
+}
+
+/// Result of `System::autodetect`.
+#[derive(Debug)]
+pub struct DetectionReport {
+	/// The probe which succeeded.
+	pub chosen: Probe,
+	/// The probes tried before, together with the reason they failed.
+	pub failures: Vec<(Probe, Error)>,
+}
+
+impl System {
+	/// Create a system using the first probe which succeeds (see `Probe::all` for the order).
+	/// If every probe fails, `Error::Autodetect` contains the reason of each failure.
+	pub fn autodetect() -> Result<(System, DetectionReport), Error> {
+		let mut failures = Vec::new();
+		for probe in Probe::all().iter() {
+			match probe.create() {
+				Ok(system) => return Ok((system, DetectionReport { chosen: *probe, failures: failures })),
+				Err(Error::AlreadyInitialized) => return Err(Error::AlreadyInitialized),
+				Err(e) => failures.push((*probe, e)),
+			}
+		}
+		Err(Error::Autodetect(failures))
+	}
+}
@@ -0,0 +1,191 @@
+use libc;
+
+use ffi;
+use error::Error;
+use std::cell::Cell;
+
+use config::{WindowConfig, SurfaceMapping, Rect, FixedRect, Overscan};
+
+/// A dispmanx element used as native window by EGL.
+pub struct Surface {
+	/// Dispmanx display.
+	display: ffi::DispmanxDisplayHandle,
+	/// The size of the display.
+	display_size: (u32, u32),
+	/// Egl-Dispmanx window.
+	window: Box<ffi::EGLDispmanxWindow>,
+	/// The mapping between the surface and the display. Changed by updates.
+	mapping: Cell<SurfaceMapping>,
+	/// The part of the display inside of the overscan.
+	safe_area: Rect,
+	/// Dispmanx resource holding the single pixel of the background.
+	background_resource: ffi::DispmanxResourceHandle,
+	/// Dispmanx element of the background, beneath the window.
+	background_element: ffi::DispmanxElementHandle,
+}
+impl Surface {
+	/// Open the display for a surface of the size given, scaled to the display as configured. The element is added by `add_elements`.
+	/// The mutex of the system must be locked.
+	pub unsafe fn new(lib_bcm_host: &ffi::LibBcmHost, display_size: (u32, u32), surface_size: (u32, u32), overscan: Overscan, config: &WindowConfig) -> Result<Self, Error> {
+		let safe_area = overscan.safe_area(display_size);
+		let source = config.source.unwrap_or(FixedRect::from(Rect::from_size(surface_size)));
+		let mapping = if config.inset_overscan {
+			let mut mapping = config.scaling.mapping(source, safe_area.size());
+			mapping.destination.x += safe_area.x;
+			mapping.destination.y += safe_area.y;
+			mapping
+		} else {
+			config.scaling.mapping(source, display_size)
+		};
+		let display = (lib_bcm_host.vc_dispmanx_display_open)(config.display.index() as libc::uint32_t);
+		if display == ffi::DISPMANX_NO_HANDLE { return Err(Error::Fn("vc_dispmanx_display_open")); }
+		Ok(Surface {
+			display: display,
+			display_size: display_size,
+			window: Box::new(ffi::EGLDispmanxWindow {
+				element: ffi::DISPMANX_NO_HANDLE,
+				width: surface_size.0 as libc::c_int,
+				height: surface_size.1 as libc::c_int,
+			}),
+			mapping: Cell::new(mapping),
+			safe_area: safe_area,
+			background_resource: ffi::DISPMANX_NO_HANDLE,
+			background_element: ffi::DISPMANX_NO_HANDLE,
+		})
+	}
+	/// Add the element and the background in an update. Elements added before a failure are kept, so they can be removed in the same update.
+	/// The mutex of the system must be locked.
+	pub unsafe fn add_elements(&mut self, lib_bcm_host: &ffi::LibBcmHost, update: ffi::DispmanxUpdateHandle, config: &WindowConfig) -> Result<(), Error> {
+		match config.background {
+			Some(color) => try!(self.add_background(lib_bcm_host, update, config.layer.saturating_sub(1), color)),
+			None => {},
+		}
+		let mapping = self.mapping.get();
+		self.window.element = try!(add_element(lib_bcm_host, update, self.display, config.layer, 0, mapping.source, mapping.destination));
+		Ok(())
+	}
+	/// Add a single colored pixel scaled to the whole display on a layer. The mutex of the system must be locked.
+	unsafe fn add_background(&mut self, lib_bcm_host: &ffi::LibBcmHost, update: ffi::DispmanxUpdateHandle, layer: i32, color: (u8, u8, u8)) -> Result<(), Error> {
+		self.background_resource = try!(color_resource(lib_bcm_host, color));
+		self.background_element = try!(add_element(lib_bcm_host, update, self.display, layer, self.background_resource, FixedRect::from(Rect::from_size((1, 1))), Rect::from_size(self.display_size)));
+		Ok(())
+	}
+	/// The native window to create the EGL surface from.
+	pub fn native_window(&self) -> ffi::EGLNativeWindowType {
+		self.window.as_ref() as *const ffi::EGLDispmanxWindow as ffi::EGLNativeWindowType
+	}
+	/// The dispmanx element.
+	pub fn element(&self) -> ffi::DispmanxElementHandle {
+		self.window.element
+	}
+	/// The mapping between the surface and the display.
+	pub fn mapping(&self) -> SurfaceMapping {
+		self.mapping.get()
+	}
+	/// The part of the display inside of the overscan.
+	pub fn safe_area(&self) -> Rect {
+		self.safe_area
+	}
+	/// Set the mapping after the element was changed.
+	pub fn set_mapping(&self, mapping: SurfaceMapping) {
+		self.mapping.set(mapping)
+	}
+	/// The size of the surface.
+	pub fn dimensions(&self) -> (u32, u32) {
+		(self.window.width as u32, self.window.height as u32)
+	}
+	/// Remove the element and the background in an update. The EGL surface must have been destroyed before. The mutex of the system must be locked.
+	pub unsafe fn remove_elements(&mut self, lib_bcm_host: &ffi::LibBcmHost, update: ffi::DispmanxUpdateHandle) -> Result<(), Error> {
+		if self.window.element != ffi::DISPMANX_NO_HANDLE {
+			if (lib_bcm_host.vc_dispmanx_element_remove)(update, self.window.element) != ffi::DISPMANX_SUCCESS { return Err(Error::Fn("vc_dispmanx_element_remove")); }
+			self.window.element = ffi::DISPMANX_NO_HANDLE;
+		}
+		if self.background_element != ffi::DISPMANX_NO_HANDLE {
+			if (lib_bcm_host.vc_dispmanx_element_remove)(update, self.background_element) != ffi::DISPMANX_SUCCESS { return Err(Error::Fn("vc_dispmanx_element_remove")); }
+			self.background_element = ffi::DISPMANX_NO_HANDLE;
+		}
+		Ok(())
+	}
+	/// Delete the background and close the display once the elements are removed. The mutex of the system must be locked.
+	pub unsafe fn close(&mut self, lib_bcm_host: &ffi::LibBcmHost) {
+		if self.background_resource != ffi::DISPMANX_NO_HANDLE {
+			assert!((lib_bcm_host.vc_dispmanx_resource_delete)(self.background_resource) == ffi::DISPMANX_SUCCESS);
+			self.background_resource = ffi::DISPMANX_NO_HANDLE;
+		}
+		if self.display != ffi::DISPMANX_NO_HANDLE {
+			assert!((lib_bcm_host.vc_dispmanx_display_close)(self.display) == ffi::DISPMANX_SUCCESS);
+			self.display = ffi::DISPMANX_NO_HANDLE;
+		}
+	}
+	/// Remove the elements in an own update and close the display. The EGL surface must have been destroyed before. The mutex of the system must be locked.
+	pub unsafe fn destroy(&mut self, lib_bcm_host: &ffi::LibBcmHost) {
+		if self.window.element != ffi::DISPMANX_NO_HANDLE || self.background_element != ffi::DISPMANX_NO_HANDLE {
+			let update = (lib_bcm_host.vc_dispmanx_update_start)(0);
+			assert!(update != ffi::DISPMANX_NO_HANDLE);
+			assert!(self.remove_elements(lib_bcm_host, update).is_ok());
+			assert!((lib_bcm_host.vc_dispmanx_update_submit_sync)(update) == ffi::DISPMANX_SUCCESS);
+		}
+		self.close(lib_bcm_host);
+	}
+}
+
+/// Create a resource holding a single pixel of a color, to be scaled by an element. The mutex of the system must be locked.
+pub unsafe fn color_resource(lib_bcm_host: &ffi::LibBcmHost, color: (u8, u8, u8)) -> Result<ffi::DispmanxResourceHandle, Error> {
+	let mut native_image: libc::uint32_t = 0;
+	let resource = (lib_bcm_host.vc_dispmanx_resource_create)(ffi::VC_IMAGE_RGB565, 1, 1, &mut native_image as *mut libc::uint32_t);
+	if resource == ffi::DISPMANX_NO_HANDLE { return Err(Error::Fn("vc_dispmanx_resource_create")); }
+	let (red, green, blue) = color;
+	let pixel = ((red as u16 >> 3) << 11) | ((green as u16 >> 2) << 5) | (blue as u16 >> 3);
+	let pixels = [pixel; 16];
+	let pixel_rect = ffi::VcRect { x: 0, y: 0, width: 1, height: 1 };
+	if (lib_bcm_host.vc_dispmanx_resource_write_data)(
+		resource,
+		ffi::VC_IMAGE_RGB565,
+		32,
+		pixels.as_ptr() as *const libc::c_void,
+		&pixel_rect as *const ffi::VcRect
+	) != ffi::DISPMANX_SUCCESS {
+		(lib_bcm_host.vc_dispmanx_resource_delete)(resource);
+		return Err(Error::Fn("vc_dispmanx_resource_write_data"));
+	}
+	Ok(resource)
+}
+
+/// Add an element showing a part of a resource (0 for the EGL surface of a window) scaled to a rectangle of the display on a layer.
+/// The mutex of the system must be locked.
+pub unsafe fn add_element(lib_bcm_host: &ffi::LibBcmHost, update: ffi::DispmanxUpdateHandle, display: ffi::DispmanxDisplayHandle, layer: i32, resource: ffi::DispmanxResourceHandle, source: FixedRect, destination: Rect) -> Result<ffi::DispmanxElementHandle, Error> {
+	let src_rect = fixed_vc_rect(source);
+	let dest_rect = vc_rect(destination);
+	let element = (lib_bcm_host.vc_dispmanx_element_add)(
+		update,
+		display,
+		layer, &dest_rect as *const ffi::VcRect,
+		resource, &src_rect as *const ffi::VcRect,
+		ffi::DISPMANX_PROTECTION_NONE,
+		0 as *mut ffi::VcDispmanxAlpha,
+		0 as *mut ffi::DispmanxClamp,
+		0
+	);
+	if element == ffi::DISPMANX_NO_HANDLE { return Err(Error::Fn("vc_dispmanx_element_add")); }
+	Ok(element)
+}
+
+/// A dispmanx rectangle in pixels, used for destinations.
+pub fn vc_rect(rect: Rect) -> ffi::VcRect {
+	ffi::VcRect {
+		x: rect.x,
+		y: rect.y,
+		width: rect.width as libc::int32_t,
+		height: rect.height as libc::int32_t,
+	}
+}
+
+/// A dispmanx rectangle in 16.16 fixed point, used for sources. Dispmanx takes sources in 16.16 fixed point and destinations in pixels.
+pub fn fixed_vc_rect(rect: FixedRect) -> ffi::VcRect {
+	ffi::VcRect {
+		x: rect.x,
+		y: rect.y,
+		width: rect.width as libc::int32_t,
+		height: rect.height as libc::int32_t,
+	}
+}
@@ -0,0 +1,307 @@
+use error::Error;
+
+/// Size of a single EDID block in bytes.
+pub const EDID_BLOCK_SIZE: usize = 128;
+
+/// Fixed header of the EDID base block.
+const EDID_HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+
+/// Tag of a CEA-861 extension block.
+const CEA_EXTENSION_TAG: u8 = 0x02;
+
+/// A detailed timing descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetailedTiming {
+	/// Pixel clock in kHz.
+	pub pixel_clock_khz: u32,
+	/// Number of visible pixels per line.
+	pub h_active: u32,
+	/// Number of blanking pixels per line.
+	pub h_blanking: u32,
+	/// Number of visible lines.
+	pub v_active: u32,
+	/// Number of blanking lines.
+	pub v_blanking: u32,
+	/// Horizontal front porch in pixels.
+	pub h_sync_offset: u32,
+	/// Horizontal sync pulse width in pixels.
+	pub h_sync_width: u32,
+	/// Vertical front porch in lines.
+	pub v_sync_offset: u32,
+	/// Vertical sync pulse width in lines.
+	pub v_sync_width: u32,
+	/// Horizontal image size in millimetres (0 if unknown).
+	pub h_size_mm: u32,
+	/// Vertical image size in millimetres (0 if unknown).
+	pub v_size_mm: u32,
+	/// Whether the timing is interlaced.
+	pub interlaced: bool,
+}
+impl DetailedTiming {
+	/// Parse an 18 byte descriptor. Returns none if the descriptor is not a detailed timing.
+	fn parse(d: &[u8]) -> Option<Self> {
+		let clock = (d[0] as u32) | ((d[1] as u32) << 8);
+		if clock == 0 { return None; }
+		Some(DetailedTiming {
+			pixel_clock_khz: clock * 10,
+			h_active: (d[2] as u32) | (((d[4] & 0xF0) as u32) << 4),
+			h_blanking: (d[3] as u32) | (((d[4] & 0x0F) as u32) << 8),
+			v_active: (d[5] as u32) | (((d[7] & 0xF0) as u32) << 4),
+			v_blanking: (d[6] as u32) | (((d[7] & 0x0F) as u32) << 8),
+			h_sync_offset: (d[8] as u32) | (((d[11] & 0xC0) as u32) << 2),
+			h_sync_width: (d[9] as u32) | (((d[11] & 0x30) as u32) << 4),
+			v_sync_offset: ((d[10] >> 4) as u32) | (((d[11] & 0x0C) as u32) << 2),
+			v_sync_width: ((d[10] & 0x0F) as u32) | (((d[11] & 0x03) as u32) << 4),
+			h_size_mm: (d[12] as u32) | (((d[14] & 0xF0) as u32) << 4),
+			v_size_mm: (d[13] as u32) | (((d[14] & 0x0F) as u32) << 8),
+			interlaced: d[17] & 0x80 != 0,
+		})
+	}
+	/// The refresh rate in Hz.
+	pub fn refresh_rate(&self) -> f32 {
+		let total = (self.h_active + self.h_blanking) * (self.v_active + self.v_blanking);
+		if total == 0 { return 0.0; }
+		let rate = (self.pixel_clock_khz as f32) * 1000.0 / (total as f32);
+		if self.interlaced { rate * 2.0 } else { rate }
+	}
+}
+
+/// Parsed extended display identification data of a connected display.
+#[derive(Debug, Clone)]
+pub struct Edid {
+	/// The raw EDID blocks (base block followed by the extension blocks).
+	pub blocks: Vec<[u8; EDID_BLOCK_SIZE]>,
+	/// Three letter PNP manufacturer id.
+	pub manufacturer: String,
+	/// Manufacturer specific product code.
+	pub product_code: u16,
+	/// Serial number (0 if unused).
+	pub serial_number: u32,
+	/// Week of manufacture (0 if unknown).
+	pub week: u8,
+	/// Year of manufacture.
+	pub year: u32,
+	/// EDID version and revision.
+	pub version: (u8, u8),
+	/// Monitor name from the display descriptors.
+	pub name: Option<String>,
+	/// Serial number string from the display descriptors.
+	pub serial_string: Option<String>,
+	/// Physical screen size in centimetres. None if unknown or if the display is a projector.
+	pub screen_size_cm: Option<(u32, u32)>,
+	/// All detailed timings of the base block and the CEA extension blocks. The first one is the native timing.
+	pub detailed_timings: Vec<DetailedTiming>,
+}
+impl Edid {
+	/// Parse raw EDID data consisting of one or more 128 byte blocks.
+	pub fn parse(data: &[u8]) -> Result<Self, Error> {
+		if data.len() < EDID_BLOCK_SIZE || data.len() % EDID_BLOCK_SIZE != 0 { return Err(Error::Edid("invalid length")); }
+		if &data[0..8] != &EDID_HEADER[..] { return Err(Error::Edid("invalid header")); }
+
+		let mut blocks = Vec::with_capacity(data.len() / EDID_BLOCK_SIZE);
+		for chunk in data.chunks(EDID_BLOCK_SIZE) {
+			if chunk.iter().fold(0u8, |sum, b| { sum.wrapping_add(*b) }) != 0 { return Err(Error::Edid("checksum mismatch")); }
+			let mut block = [0u8; EDID_BLOCK_SIZE];
+			block.copy_from_slice(chunk);
+			blocks.push(block);
+		}
+
+		let (manufacturer, product_code, serial_number, week, year, version, screen_size_cm) = {
+			let base = &blocks[0];
+			let id = ((base[8] as u16) << 8) | (base[9] as u16);
+			let manufacturer: String = [10, 5, 0].iter()
+				.map(|shift| { (b'A' - 1 + ((id >> *shift) & 0x1F) as u8) as char })
+				.collect();
+			let screen_size_cm = match (base[21], base[22]) {
+				(0, _) | (_, 0) => None,
+				(w, h) => Some((w as u32, h as u32)),
+			};
+			(
+				manufacturer,
+				(base[10] as u16) | ((base[11] as u16) << 8),
+				(base[12] as u32) | ((base[13] as u32) << 8) | ((base[14] as u32) << 16) | ((base[15] as u32) << 24),
+				base[16],
+				base[17] as u32 + 1990,
+				(base[18], base[19]),
+				screen_size_cm,
+			)
+		};
+
+		let mut name = None;
+		let mut serial_string = None;
+		let mut detailed_timings = Vec::new();
+		for descriptor in blocks[0][54..126].chunks(18) {
+			match DetailedTiming::parse(descriptor) {
+				Some(timing) => detailed_timings.push(timing),
+				None => match descriptor[3] {
+					0xFC => name = Some(descriptor_text(descriptor)),
+					0xFF => serial_string = Some(descriptor_text(descriptor)),
+					_ => {},
+				},
+			}
+		}
+		for block in blocks[1..].iter().filter(|b| { b[0] == CEA_EXTENSION_TAG }) {
+			// Detailed timings start at the offset stored in byte 2 and end before the checksum.
+			let start = block[2] as usize;
+			if start < 4 { continue; }
+			let mut offset = start;
+			while offset + 18 < EDID_BLOCK_SIZE {
+				match DetailedTiming::parse(&block[offset..offset + 18]) {
+					Some(timing) => detailed_timings.push(timing),
+					None => break,
+				}
+				offset += 18;
+			}
+		}
+
+		Ok(Edid {
+			blocks: blocks,
+			manufacturer: manufacturer,
+			product_code: product_code,
+			serial_number: serial_number,
+			week: week,
+			year: year,
+			version: version,
+			name: name,
+			serial_string: serial_string,
+			screen_size_cm: screen_size_cm,
+			detailed_timings: detailed_timings,
+		})
+	}
+	/// Number of extension blocks announced by the base block.
+	pub fn extension_count(&self) -> usize {
+		self.blocks[0][126] as usize
+	}
+	/// The native (preferred) timing of the display.
+	pub fn native_timing(&self) -> Option<&DetailedTiming> {
+		self.detailed_timings.first()
+	}
+	/// Physical size of the visible area in millimetres. Uses the native timing if it provides a size, the screen size otherwise.
+	pub fn physical_size_mm(&self) -> Option<(u32, u32)> {
+		match self.native_timing() {
+			Some(t) if t.h_size_mm != 0 && t.v_size_mm != 0 => Some((t.h_size_mm, t.v_size_mm)),
+			_ => self.screen_size_cm.map(|(w, h)| { (w * 10, h * 10) }),
+		}
+	}
+	/// Horizontal and vertical dots per inch for a display of the given size in pixels (e.g. from `System::display_size`).
+	pub fn dpi(&self, size: (u32, u32)) -> Option<(f32, f32)> {
+		self.physical_size_mm().map(|(w, h)| {
+			(size.0 as f32 * 25.4 / w as f32, size.1 as f32 * 25.4 / h as f32)
+		})
+	}
+}
+
+// Extract the text of a display descriptor (terminated by a line feed and padded with spaces).
+fn descriptor_text(descriptor: &[u8]) -> String {
+	descriptor[5..18].iter()
+		.take_while(|b| { **b != 0x0A })
+		.map(|b| { *b as char })
+		.collect::<String>()
+		.trim_right()
+		.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use error::Error;
+
+	// Set the checksum byte of a block.
+	fn seal(block: &mut [u8]) {
+		let sum = block[..EDID_BLOCK_SIZE - 1].iter().fold(0u8, |sum, b| { sum.wrapping_add(*b) });
+		block[EDID_BLOCK_SIZE - 1] = 0u8.wrapping_sub(sum);
+	}
+
+	// A base block of a 1920x1080 display named "TEST" by manufacturer "ABC".
+	fn base_block() -> Vec<u8> {
+		let mut block = vec![0u8; EDID_BLOCK_SIZE];
+		block[0..8].copy_from_slice(&EDID_HEADER);
+		// "ABC": 00001 00010 00011
+		block[8] = 0x04;
+		block[9] = 0x43;
+		block[10] = 0x34;
+		block[11] = 0x12;
+		block[17] = 30;
+		block[18] = 1;
+		block[19] = 3;
+		block[21] = 53;
+		block[22] = 30;
+		// 1920x1080 at 148.5 MHz with a 527x296 mm image.
+		block[54..72].copy_from_slice(&[0x02, 0x3A, 0x80, 0x18, 0x71, 0x38, 0x2D, 0x40, 0x58, 0x2C, 0x45, 0x00, 0x0F, 0x28, 0x21, 0x00, 0x00, 0x1E]);
+		block[72..90].copy_from_slice(&[0, 0, 0, 0xFC, 0, b'T', b'E', b'S', b'T', 0x0A, b' ', b' ', b' ', b' ', b' ', b' ', b' ', b' ']);
+		seal(&mut block);
+		block
+	}
+
+	#[test]
+	fn parse_base_block() {
+		let edid = Edid::parse(&base_block()).unwrap();
+		assert_eq!(edid.manufacturer, "ABC");
+		assert_eq!(edid.product_code, 0x1234);
+		assert_eq!(edid.year, 2020);
+		assert_eq!(edid.version, (1, 3));
+		assert_eq!(edid.name, Some("TEST".to_string()));
+		assert_eq!(edid.screen_size_cm, Some((53, 30)));
+		let timing = *edid.native_timing().unwrap();
+		assert_eq!((timing.h_active, timing.v_active), (1920, 1080));
+		assert_eq!(timing.pixel_clock_khz, 148500);
+		assert_eq!(timing.refresh_rate().round(), 60.0);
+		assert_eq!(edid.physical_size_mm(), Some((527, 296)));
+	}
+
+	#[test]
+	fn parse_cea_extension() {
+		let mut data = base_block();
+		data[126] = 1;
+		seal(&mut data);
+		let mut extension = vec![0u8; EDID_BLOCK_SIZE];
+		extension[0] = CEA_EXTENSION_TAG;
+		extension[1] = 3;
+		extension[2] = 4;
+		// 1280x720 at 74.25 MHz.
+		extension[4..22].copy_from_slice(&[0x01, 0x1D, 0x00, 0x72, 0x51, 0xD0, 0x1E, 0x20, 0x6E, 0x28, 0x55, 0x00, 0, 0, 0, 0, 0, 0x1E]);
+		seal(&mut extension);
+		data.extend_from_slice(&extension);
+		let edid = Edid::parse(&data).unwrap();
+		assert_eq!(edid.extension_count(), 1);
+		assert_eq!(edid.blocks.len(), 2);
+		assert_eq!(edid.detailed_timings.len(), 2);
+		assert_eq!((edid.detailed_timings[1].h_active, edid.detailed_timings[1].v_active), (1280, 720));
+	}
+
+	#[test]
+	fn reject_truncated_blocks() {
+		let data = base_block();
+		match Edid::parse(&data[..100]) {
+			Err(Error::Edid("invalid length")) => {},
+			r => panic!("unexpected result {:?}", r.map(|_| { () })),
+		}
+		let mut data = base_block();
+		data.extend_from_slice(&[0u8; 10]);
+		match Edid::parse(&data) {
+			Err(Error::Edid("invalid length")) => {},
+			r => panic!("unexpected result {:?}", r.map(|_| { () })),
+		}
+	}
+
+	#[test]
+	fn reject_bad_header() {
+		let mut data = base_block();
+		data[1] = 0;
+		seal(&mut data);
+		match Edid::parse(&data) {
+			Err(Error::Edid("invalid header")) => {},
+			r => panic!("unexpected result {:?}", r.map(|_| { () })),
+		}
+	}
+
+	#[test]
+	fn reject_bad_checksum() {
+		let mut data = base_block();
+		data[127] = data[127].wrapping_add(1);
+		match Edid::parse(&data) {
+			Err(Error::Edid("checksum mismatch")) => {},
+			r => panic!("unexpected result {:?}", r.map(|_| { () })),
+		}
+	}
+}
@@ -1,6 +1,11 @@
+use std::io;
+use std::fmt;
+use std::error;
+
 use shared_library;
 
 use ffi;
+use detect::Probe;
 
 /// Possible errors.
 #[derive(Debug)]
@@ -13,7 +18,96 @@ pub enum Error {
 	Sl(shared_library::LoadingError),
 	/// Dynamic library error.
 	Dl(String),
+	/// Io error, e.g. when opening a device.
+	Io(io::Error),
+	/// Malformed EDID data with a description of the problem.
+	Edid(&'static str),
+	/// The functionality is not available for the requested device.
+	Unsupported(&'static str),
+	/// A system exists already. Only one system may exist at a time.
+	AlreadyInitialized,
+	/// Every probe of `System::autodetect` failed. Contains the reason of each failure.
+	Autodetect(Vec<(Probe, Error)>),
+	/// Invalid configuration file or environment variable with a description of the problem.
+	Config(String),
+}
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::Gl(name, code) => match code_name(code) {
+				Some(code_name) => write!(f, "{} failed with {} (0x{:04X})", name, code_name, code),
+				None => write!(f, "{} failed with error 0x{:04X}", name, code),
+			},
+			Error::Fn(name) => write!(f, "{} failed", name),
+			Error::Sl(shared_library::LoadingError::LibraryNotFound { ref descr }) => write!(f, "library not found: {}", descr),
+			Error::Sl(shared_library::LoadingError::SymbolNotFound { symbol }) => write!(f, "symbol not found: {}", symbol),
+			Error::Dl(ref message) => write!(f, "loading a library failed: {}", message),
+			Error::Io(ref e) => write!(f, "{}", e),
+			Error::Edid(problem) => write!(f, "malformed EDID: {}", problem),
+			Error::Unsupported(what) => write!(f, "unsupported: {}", what),
+			Error::AlreadyInitialized => write!(f, "a system exists already"),
+			Error::Autodetect(ref failures) => {
+				try!(write!(f, "no backend available"));
+				for &(ref probe, ref error) in failures {
+					try!(write!(f, "; {:?}: {}", probe, error));
+				}
+				Ok(())
+			},
+			Error::Config(ref problem) => write!(f, "invalid configuration: {}", problem),
+		}
+	}
 }
+impl error::Error for Error {
+	fn description(&self) -> &str {
+		match *self {
+			Error::Gl(..) => "OpenGL error",
+			Error::Fn(..) => "function call failed",
+			Error::Sl(..) | Error::Dl(..) => "loading a library failed",
+			Error::Io(..) => "io error",
+			Error::Edid(..) => "malformed EDID",
+			Error::Unsupported(..) => "unsupported",
+			Error::AlreadyInitialized => "a system exists already",
+			Error::Autodetect(..) => "no backend available",
+			Error::Config(..) => "invalid configuration",
+		}
+	}
+	fn cause(&self) -> Option<&error::Error> {
+		match *self {
+			Error::Io(ref e) => Some(e),
+			_ => None,
+		}
+	}
+}
+
+// Name of a GL or EGL error code.
+fn code_name(code: ffi::GLenum) -> Option<&'static str> {
+	Some(match code {
+		0x0500 => "GL_INVALID_ENUM",
+		0x0501 => "GL_INVALID_VALUE",
+		0x0502 => "GL_INVALID_OPERATION",
+		0x0503 => "GL_STACK_OVERFLOW",
+		0x0504 => "GL_STACK_UNDERFLOW",
+		0x0505 => "GL_OUT_OF_MEMORY",
+		0x0506 => "GL_INVALID_FRAMEBUFFER_OPERATION",
+		0x0507 => "GL_CONTEXT_LOST",
+		0x3001 => "EGL_NOT_INITIALIZED",
+		0x3002 => "EGL_BAD_ACCESS",
+		0x3003 => "EGL_BAD_ALLOC",
+		0x3004 => "EGL_BAD_ATTRIBUTE",
+		0x3005 => "EGL_BAD_CONFIG",
+		0x3006 => "EGL_BAD_CONTEXT",
+		0x3007 => "EGL_BAD_CURRENT_SURFACE",
+		0x3008 => "EGL_BAD_DISPLAY",
+		0x3009 => "EGL_BAD_MATCH",
+		0x300A => "EGL_BAD_NATIVE_PIXMAP",
+		0x300B => "EGL_BAD_NATIVE_WINDOW",
+		0x300C => "EGL_BAD_PARAMETER",
+		0x300D => "EGL_BAD_SURFACE",
+		0x300E => "EGL_CONTEXT_LOST",
+		_ => return None,
+	})
+}
+
 // Function used to check whether an opengl error is present.
 pub unsafe fn gl_error(lib_glesv2: &ffi::LibGLESv2, name: &'static str) -> Result<(), Error> {
 	let error = (lib_glesv2.glGetError)();
@@ -0,0 +1,173 @@
+use std::rc::Rc;
+use std::sync::Arc;
+use std::ops::Deref;
+use std::iter;
+
+use glium;
+
+use error::Error;
+use config::WindowConfig;
+use super::{System, Window};
+use glium::backend::{Context, Facade};
+
+/// Options for the creation of a glium facade.
+pub struct FacadeOptions {
+	/// Whether glium checks that the context is current before every call. Defaults to true.
+	pub check_current_context: bool,
+	/// The handling of debug messages. Defaults to glium's default behavior.
+	pub debug: glium::debug::DebugCallbackBehavior,
+}
+impl FacadeOptions {
+	/// Enable or disable the check of the current context before every call.
+	/// Disabling it saves a call per operation, but the window must stay current on the rendering thread.
+	pub fn with_check_current_context(mut self, check: bool) -> Self {
+		self.check_current_context = check;
+		self
+	}
+	/// Set the handling of debug messages.
+	pub fn with_debug(mut self, debug: glium::debug::DebugCallbackBehavior) -> Self {
+		self.debug = debug;
+		self
+	}
+}
+impl Default for FacadeOptions {
+	fn default() -> Self {
+		FacadeOptions {
+			check_current_context: true,
+			debug: Default::default(),
+		}
+	}
+}
+
+/// Types of glium facades.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FacadeType {
+	/// The bare glium context, as returned by `create_facade`.
+	Context,
+	/// The display-like wrapper `PiDisplay`, which owns its window.
+	Display,
+}
+
+/// Creates a new glium facade from a window. The facade can only be used on the thread the window is current on.
+pub fn create_facade<S>(window: Window<S>) -> Result<Rc<glium::backend::Context>, glium::GliumCreationError<Error>> where S: Deref<Target=System> + 'static {
+	create_facade_with_options(window, Default::default())
+}
+/// Creates a new glium facade from a window with the given handling of debug messages.
+/// Request a debug context (`WindowConfig::debug`) to receive the messages of the driver.
+pub fn create_facade_with_debug<S>(window: Window<S>, debug: glium::debug::DebugCallbackBehavior) -> Result<Rc<glium::backend::Context>, glium::GliumCreationError<Error>> where S: Deref<Target=System> + 'static {
+	create_facade_with_options(window, FacadeOptions::default().with_debug(debug))
+}
+/// Creates a new glium facade from a window with the given options.
+pub fn create_facade_with_options<S>(window: Window<S>, options: FacadeOptions) -> Result<Rc<glium::backend::Context>, glium::GliumCreationError<Error>> where S: Deref<Target=System> + 'static {
+	let window = Rc::new(window);
+	unsafe { glium::backend::Context::new::<Rc<Window<S>>, Error>(window, options.check_current_context, options.debug) }
+}
+/// Creates a new glium facade.
+pub fn create_window_facade(system: &Arc<System>, config: &WindowConfig) -> Result<Rc<glium::backend::Context>, glium::GliumCreationError<Error>> {
+	create_window_facade_with_options(system, config, Default::default())
+}
+/// Creates a new glium facade with the given options.
+pub fn create_window_facade_with_options(system: &Arc<System>, config: &WindowConfig, options: FacadeOptions) -> Result<Rc<glium::backend::Context>, glium::GliumCreationError<Error>> {
+	let window = try!(Window::new(system.clone(), config).map_err(|e| { glium::GliumCreationError::BackendCreationError(e) }));
+	create_facade_with_options(window, options)
+}
+
+/// A glium facade which owns its window, similar to the glutin display of glium.
+/// Can only be used on the thread the window is current on.
+pub struct PiDisplay {
+	/// The glium context.
+	context: Rc<Context>,
+	/// The window. Shared with the context, which uses it as backend.
+	window: Rc<Window<Arc<System>>>,
+}
+impl PiDisplay {
+	/// Create a window and a display for it.
+	pub fn new(system: &Arc<System>, config: &WindowConfig) -> Result<Self, glium::GliumCreationError<Error>> {
+		PiDisplay::with_options(system, config, Default::default())
+	}
+	/// Create a window and a display for it with the given options.
+	pub fn with_options(system: &Arc<System>, config: &WindowConfig, options: FacadeOptions) -> Result<Self, glium::GliumCreationError<Error>> {
+		let window = try!(Window::new(system.clone(), config).map_err(|e| { glium::GliumCreationError::BackendCreationError(e) }));
+		PiDisplay::from_window(window, options)
+	}
+	/// Create a display for an existing window.
+	pub fn from_window(window: Window<Arc<System>>, options: FacadeOptions) -> Result<Self, glium::GliumCreationError<Error>> {
+		let window = Rc::new(window);
+		let context = try!(unsafe { Context::new::<Rc<Window<Arc<System>>>, Error>(window.clone(), options.check_current_context, options.debug) });
+		Ok(PiDisplay {
+			context: context,
+			window: window,
+		})
+	}
+	/// Start drawing on the window. Call `finish` on the frame to swap the buffers.
+	pub fn draw(&self) -> glium::Frame {
+		glium::Frame::new(self.context.clone(), self.context.get_framebuffer_dimensions())
+	}
+	/// The window drawn on.
+	pub fn window(&self) -> &Window<Arc<System>> {
+		&self.window
+	}
+	/// The system the window belongs to.
+	pub fn system(&self) -> &Arc<System> {
+		&self.window.system
+	}
+	/// Events of the window. There is no event mechanism without X, so there are never any events.
+	/// Provided to be able to handle the glutin display and this display the same way.
+	pub fn poll_events(&self) -> iter::Empty<glium::glutin::Event> {
+		iter::empty()
+	}
+}
+impl Facade for PiDisplay {
+	fn get_context(&self) -> &Rc<Context> {
+		&self.context
+	}
+}
+impl Deref for PiDisplay {
+	type Target = Context;
+	fn deref(&self) -> &Context {
+		&self.context
+	}
+}
+
+/// A glium facade of the type chosen on creation.
+pub enum PiFacade {
+	/// The bare glium context.
+	Context(Rc<Context>),
+	/// The display-like wrapper.
+	Display(PiDisplay),
+}
+impl PiFacade {
+	/// Create a window and a facade of a type for it.
+	pub fn new(system: &Arc<System>, config: &WindowConfig, facade_type: FacadeType, options: FacadeOptions) -> Result<Self, glium::GliumCreationError<Error>> {
+		let window = try!(Window::new(system.clone(), config).map_err(|e| { glium::GliumCreationError::BackendCreationError(e) }));
+		PiFacade::from_window(window, facade_type, options)
+	}
+	/// Create a facade of a type for an existing window.
+	pub fn from_window(window: Window<Arc<System>>, facade_type: FacadeType, options: FacadeOptions) -> Result<Self, glium::GliumCreationError<Error>> {
+		match facade_type {
+			FacadeType::Context => create_facade_with_options(window, options).map(PiFacade::Context),
+			FacadeType::Display => PiDisplay::from_window(window, options).map(PiFacade::Display),
+		}
+	}
+	/// The display-like wrapper. None for a bare context.
+	pub fn display(&self) -> Option<&PiDisplay> {
+		match *self {
+			PiFacade::Context(_) => None,
+			PiFacade::Display(ref display) => Some(display),
+		}
+	}
+}
+impl Facade for PiFacade {
+	fn get_context(&self) -> &Rc<Context> {
+		match *self {
+			PiFacade::Context(ref context) => context,
+			PiFacade::Display(ref display) => display.get_context(),
+		}
+	}
+}
+impl Deref for PiFacade {
+	type Target = Context;
+	fn deref(&self) -> &Context {
+		self.get_context()
+	}
+}
@@ -8,6 +8,9 @@ pub type DispmanxProtection = libc::uint32_t;
 pub type DispmanxTransform = libc::c_int;
 pub type DispmanxClamp = libc::c_void;
 pub type VcDispmanxAlpha = libc::c_void;
+pub type VcImageType = libc::c_int;
+pub type VcGencmd = unsafe extern "C" fn(response: *mut libc::c_char, maxlen: libc::c_int, format: *const libc::c_char, ...) -> libc::c_int;
+pub type DispmanxCallbackFunc = extern "C" fn(update: DispmanxUpdateHandle, arg: *mut libc::c_void);
 
 #[repr(C)]
 pub struct VcRect {
@@ -20,6 +23,42 @@ pub struct VcRect {
 pub const DISPMANX_PROTECTION_NONE: DispmanxProtection = 0 as DispmanxProtection;
 pub const DISPMANX_SUCCESS: libc::c_int = 0 as libc::c_int;
 pub const DISPMANX_NO_HANDLE: libc::uint32_t = 0 as libc::uint32_t;
+pub const DISPMANX_ELEMENT_CHANGE_LAYER: libc::uint32_t = 1 << 0;
+pub const DISPMANX_ELEMENT_CHANGE_OPACITY: libc::uint32_t = 1 << 1;
+pub const DISPMANX_ELEMENT_CHANGE_DEST_RECT: libc::uint32_t = 1 << 2;
+pub const DISPMANX_ELEMENT_CHANGE_SRC_RECT: libc::uint32_t = 1 << 3;
+pub const VC_IMAGE_RGB565: VcImageType = 1;
+pub const VC_IMAGE_YUV420: VcImageType = 3;
+pub const VC_IMAGE_RGBA32: VcImageType = 15;
+
+pub type SdtvMode = libc::uint32_t;
+pub const VC_SDTV_NTSC: libc::uint32_t = 1 << 18;
+pub const VC_SDTV_PAL: libc::uint32_t = 1 << 19;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SdtvOptions {
+	pub aspect: libc::uint32_t,
+}
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SdtvDisplayState {
+	pub state: libc::uint32_t,
+	pub width: libc::uint32_t,
+	pub height: libc::uint32_t,
+	pub frame_rate: libc::uint16_t,
+	pub scan_mode: libc::uint16_t,
+	pub mode: SdtvMode,
+	pub display_options: SdtvOptions,
+	pub cp_mode: libc::uint32_t,
+}
+// The display union also holds the larger Hdmi state, which is not used.
+#[repr(C)]
+pub struct TvDisplayState {
+	pub state: libc::uint32_t,
+	pub sdtv: SdtvDisplayState,
+	pub padding: [libc::uint32_t; 16],
+}
 
 #[repr(C)]
 pub struct EGLDispmanxWindow {
@@ -28,7 +67,7 @@ pub struct EGLDispmanxWindow {
 	pub height: libc::c_int,
 }
 pub type EGLNativeDisplayType = *const libc::c_void;
-pub type EGLNativeWindowType = *const EGLDispmanxWindow;
+pub type EGLNativeWindowType = *const libc::c_void;
 pub type EGLConfig = *const libc::c_void;
 pub type EGLSurface = *const libc::c_void;
 pub type EGLContext = *const libc::c_void;
@@ -44,15 +83,86 @@ pub const EGL_BLUE_SIZE: GLenum = 0x3022;
 pub const EGL_GREEN_SIZE: GLenum = 0x3023; 
 pub const EGL_RED_SIZE: GLenum = 0x3024; 
 pub const EGL_DEPTH_SIZE: GLenum = 0x3025;
+pub const EGL_STENCIL_SIZE: GLenum = 0x3026;
+pub const EGL_CONFIG_ID: GLenum = 0x3028;
+pub const EGL_SAMPLES: GLenum = 0x3031;
+pub const EGL_SAMPLE_BUFFERS: GLenum = 0x3032;
+pub const EGL_NATIVE_VISUAL_ID: GLenum = 0x302E;
 pub const EGL_SURFACE_TYPE: GLenum = 0x3033;
+pub const EGL_PBUFFER_BIT: GLenum = 0x0001;
 pub const EGL_WINDOW_BIT: GLenum = 0x0004;
+pub const EGL_VENDOR: GLenum = 0x3053;
+pub const EGL_VERSION: GLenum = 0x3054;
+pub const EGL_EXTENSIONS: GLenum = 0x3055;
+pub const EGL_CLIENT_APIS: GLenum = 0x308D;
+pub const EGL_HEIGHT: GLenum = 0x3056;
+pub const EGL_WIDTH: GLenum = 0x3057;
+pub const EGL_PLATFORM_SURFACELESS_MESA: EGLenum = 0x31DD;
 pub const EGL_NONE: GLenum = 0x3038;
 pub const EGL_OPENGL_ES_API: GLenum = 0x30A0; 
 pub const EGL_NO_CONTEXT: EGLContext = 0 as EGLContext; 
 pub const EGL_CONTEXT_CLIENT_VERSION: GLenum = 0x3098;
+pub const EGL_RENDERABLE_TYPE: GLenum = 0x3040;
+pub const EGL_OPENGL_ES3_BIT_KHR: GLenum = 0x0040;
+pub const EGL_CONTEXT_MAJOR_VERSION_KHR: GLenum = 0x3098;
+pub const EGL_CONTEXT_MINOR_VERSION_KHR: GLenum = 0x30FB;
+pub const EGL_CONTEXT_FLAGS_KHR: GLenum = 0x30FC;
+pub const EGL_CONTEXT_OPENGL_DEBUG_BIT_KHR: GLenum = 0x0001;
+pub const EGL_CONTEXT_OPENGL_ROBUST_ACCESS_EXT: GLenum = 0x30BF;
+pub const EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_EXT: GLenum = 0x3138;
+pub const EGL_NO_RESET_NOTIFICATION_EXT: GLenum = 0x31BE;
+pub const EGL_LOSE_CONTEXT_ON_RESET_EXT: GLenum = 0x31BF;
+pub const EGL_CONTEXT_OPENGL_DEBUG: GLenum = 0x31B0;
+pub const EGL_CONTEXT_OPENGL_ROBUST_ACCESS: GLenum = 0x31B2;
+pub const EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY: GLenum = 0x31BD;
+pub const EGL_TRUE: GLenum = 1;
 pub const EGL_NO_SURFACE: EGLSurface = 0 as EGLSurface;
+pub const EGL_DRAW: GLenum = 0x3059;
+pub const EGL_READ: GLenum = 0x305A;
 
 pub type GLenum = libc::c_uint;
+pub type GLubyte = libc::c_uchar;
+pub type GLuint = libc::c_uint;
+pub type GLint = libc::c_int;
+pub type GLsizei = libc::c_int;
+
+pub const GL_VENDOR: GLenum = 0x1F00;
+pub const GL_RENDERER: GLenum = 0x1F01;
+pub const GL_VERSION: GLenum = 0x1F02;
+pub const GL_EXTENSIONS: GLenum = 0x1F03;
+pub const GL_SHADING_LANGUAGE_VERSION: GLenum = 0x8B8C;
+pub const GL_TEXTURE_2D: GLenum = 0x0DE1;
+pub const GL_TEXTURE_BINDING_2D: GLenum = 0x8069;
+pub const GL_TEXTURE_EXTERNAL_OES: GLenum = 0x8D65;
+pub const GL_TEXTURE_BINDING_EXTERNAL_OES: GLenum = 0x8D67;
+pub const GL_TEXTURE_MAG_FILTER: GLenum = 0x2800;
+pub const GL_TEXTURE_MIN_FILTER: GLenum = 0x2801;
+pub const GL_TEXTURE_WRAP_S: GLenum = 0x2802;
+pub const GL_TEXTURE_WRAP_T: GLenum = 0x2803;
+pub const GL_LINEAR: GLenum = 0x2601;
+pub const GL_CLAMP_TO_EDGE: GLenum = 0x812F;
+
+// EGL_KHR_image_base
+pub type EGLImageKHR = *const libc::c_void;
+pub type EGLClientBuffer = *const libc::c_void;
+pub const EGL_NO_IMAGE_KHR: EGLImageKHR = 0 as EGLImageKHR;
+pub const EGL_IMAGE_PRESERVED_KHR: GLenum = 0x30D2;
+pub const EGL_IMAGE_BRCM_DISPMANX: EGLenum = 0x99930A;
+pub const EGL_IMAGE_BRCM_MULTIMEDIA: EGLenum = 0x99930B;
+// EGL_EXT_image_dma_buf_import
+pub const EGL_LINUX_DMA_BUF_EXT: EGLenum = 0x3270;
+pub const EGL_LINUX_DRM_FOURCC_EXT: GLenum = 0x3271;
+pub const EGL_DMA_BUF_PLANE0_FD_EXT: GLenum = 0x3272;
+pub const EGL_DMA_BUF_PLANE0_OFFSET_EXT: GLenum = 0x3273;
+pub const EGL_DMA_BUF_PLANE0_PITCH_EXT: GLenum = 0x3274;
+// Loaded with eglGetProcAddress.
+pub type EglCreateImageKhr = unsafe extern "C" fn(display: EGLDisplay, context: EGLContext, target: EGLenum, buffer: EGLClientBuffer, attrib_list: *const EGLint) -> EGLImageKHR;
+pub type EglDestroyImageKhr = unsafe extern "C" fn(display: EGLDisplay, image: EGLImageKHR) -> EGLBoolean;
+// GL_OES_EGL_image, loaded with eglGetProcAddress.
+pub type GlEglImageTargetTexture2dOes = unsafe extern "C" fn(target: GLenum, image: EGLImageKHR);
+
+// EGL_EXT_platform_base, loaded with eglGetProcAddress.
+pub type EglGetPlatformDisplayExt = unsafe extern "C" fn(platform: EGLenum, native_display: *const libc::c_void, attrib_list: *const EGLint) -> EGLDisplay;
 
 shared_library!(LibBcmHost,
 	pub fn bcm_host_init(),
@@ -65,6 +175,7 @@ shared_library!(LibBcmHost,
 	pub fn vc_dispmanx_display_close(handle: DispmanxDisplayHandle) -> libc::c_int,
 	pub fn vc_dispmanx_update_start(priority: libc::int32_t) -> DispmanxUpdateHandle,
 	pub fn vc_dispmanx_update_submit_sync(update: DispmanxUpdateHandle) -> libc::c_int,
+	pub fn vc_dispmanx_update_submit(update: DispmanxUpdateHandle, cb_func: DispmanxCallbackFunc, cb_arg: *mut libc::c_void) -> libc::c_int,
 	pub fn vc_dispmanx_element_add(
 		update: DispmanxUpdateHandle, display: DispmanxDisplayHandle,
 		layer: libc::int32_t, dest_rect: *const VcRect, src: DispmanxResourceHandle,
@@ -73,10 +184,38 @@ shared_library!(LibBcmHost,
 		clamp: *mut DispmanxClamp, transform: DispmanxTransform
 	) -> DispmanxElementHandle,
 	pub fn vc_dispmanx_element_remove(update: DispmanxUpdateHandle, element: DispmanxElementHandle) -> libc::c_int,
+	pub fn vc_dispmanx_element_change_attributes(
+		update: DispmanxUpdateHandle, element: DispmanxElementHandle,
+		change_flags: libc::uint32_t, layer: libc::int32_t, opacity: libc::uint8_t,
+		dest_rect: *const VcRect, src_rect: *const VcRect,
+		mask: DispmanxResourceHandle, transform: DispmanxTransform
+	) -> libc::c_int,
+	pub fn vc_dispmanx_resource_create(
+		image_type: VcImageType, width: libc::uint32_t, height: libc::uint32_t,
+		native_image_handle: *mut libc::uint32_t
+	) -> DispmanxResourceHandle,
+	pub fn vc_dispmanx_resource_write_data(
+		res: DispmanxResourceHandle, src_type: VcImageType, src_pitch: libc::c_int,
+		src_address: *const libc::c_void, rect: *const VcRect
+	) -> libc::c_int,
+	pub fn vc_dispmanx_resource_delete(res: DispmanxResourceHandle) -> libc::c_int,
+	pub fn vc_tv_power_off() -> libc::c_int,
+	pub fn vc_tv_hdmi_power_on_preferred() -> libc::c_int,
+	pub fn vc_tv_sdtv_power_on(mode: SdtvMode, options: *const SdtvOptions) -> libc::c_int,
+	pub fn vc_tv_get_display_state(state: *mut TvDisplayState) -> libc::c_int,
+	pub fn vc_tv_hdmi_ddc_read(offset: libc::uint32_t, length: libc::uint32_t, buffer: *mut libc::uint8_t) -> libc::c_int,
+	// Variadic, so it is loaded as a function pointer.
+	pub static vc_gencmd: VcGencmd,
 );
 
 shared_library!(LibGLESv2,
 	pub fn glGetError() -> GLenum,
+	pub fn glGetString(name: GLenum) -> *const GLubyte,
+	pub fn glGetIntegerv(name: GLenum, data: *mut GLint),
+	pub fn glGenTextures(n: GLsizei, textures: *mut GLuint),
+	pub fn glDeleteTextures(n: GLsizei, textures: *const GLuint),
+	pub fn glBindTexture(target: GLenum, texture: GLuint),
+	pub fn glTexParameteri(target: GLenum, name: GLenum, param: GLint),
 );
 
 shared_library!(LibEGL,
@@ -92,5 +231,345 @@ shared_library!(LibEGL,
 	pub fn eglMakeCurrent(display: EGLDisplay, draw: EGLSurface, read: EGLSurface, context: EGLContext) -> EGLBoolean,
 	pub fn eglSwapBuffers(display: EGLDisplay, draw: EGLSurface) -> EGLBoolean,
 	pub fn eglGetCurrentContext() -> EGLContext,
+	pub fn eglGetCurrentSurface(readdraw: EGLint) -> EGLSurface,
+	pub fn eglReleaseThread() -> EGLBoolean,
+	pub fn eglCreatePbufferSurface(display: EGLDisplay, config: EGLConfig, attrib_list: *const EGLint) -> EGLSurface,
+	pub fn eglGetProcAddress(procname: *const libc::c_char) -> *const libc::c_void,
+	pub fn eglGetConfigAttrib(display: EGLDisplay, config: EGLConfig, attribute: EGLint, value: *mut EGLint) -> EGLBoolean,
+	pub fn eglSwapInterval(display: EGLDisplay, interval: EGLint) -> EGLBoolean,
+	pub fn eglGetConfigs(display: EGLDisplay, configs: *mut EGLConfig, config_size: EGLint, num_config: *mut EGLint) -> EGLBoolean,
+	pub fn eglQueryString(display: EGLDisplay, name: EGLint) -> *const libc::c_char,
+);
+
+
+pub const DRM_MODE_CONNECTED: libc::c_int = 1;
+pub const DRM_MODE_TYPE_PREFERRED: libc::uint32_t = 1 << 3;
+pub const DRM_MODE_PAGE_FLIP_EVENT: libc::uint32_t = 0x01;
+pub const DRM_DISPLAY_MODE_LEN: usize = 32;
+pub const DRM_PROP_NAME_LEN: usize = 32;
+pub const DRM_EVENT_CONTEXT_VERSION: libc::c_int = 2;
+
+#[repr(C)]
+pub struct DrmModeRes {
+	pub count_fbs: libc::c_int,
+	pub fbs: *mut libc::uint32_t,
+	pub count_crtcs: libc::c_int,
+	pub crtcs: *mut libc::uint32_t,
+	pub count_connectors: libc::c_int,
+	pub connectors: *mut libc::uint32_t,
+	pub count_encoders: libc::c_int,
+	pub encoders: *mut libc::uint32_t,
+	pub min_width: libc::uint32_t,
+	pub max_width: libc::uint32_t,
+	pub min_height: libc::uint32_t,
+	pub max_height: libc::uint32_t,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct DrmModeModeInfo {
+	pub clock: libc::uint32_t,
+	pub hdisplay: libc::uint16_t,
+	pub hsync_start: libc::uint16_t,
+	pub hsync_end: libc::uint16_t,
+	pub htotal: libc::uint16_t,
+	pub hskew: libc::uint16_t,
+	pub vdisplay: libc::uint16_t,
+	pub vsync_start: libc::uint16_t,
+	pub vsync_end: libc::uint16_t,
+	pub vtotal: libc::uint16_t,
+	pub vscan: libc::uint16_t,
+	pub vrefresh: libc::uint32_t,
+	pub flags: libc::uint32_t,
+	pub mode_type: libc::uint32_t,
+	pub name: [libc::c_char; DRM_DISPLAY_MODE_LEN],
+}
+
+#[repr(C)]
+pub struct DrmModeConnector {
+	pub connector_id: libc::uint32_t,
+	pub encoder_id: libc::uint32_t,
+	pub connector_type: libc::uint32_t,
+	pub connector_type_id: libc::uint32_t,
+	pub connection: libc::c_int,
+	pub mm_width: libc::uint32_t,
+	pub mm_height: libc::uint32_t,
+	pub subpixel: libc::c_int,
+	pub count_modes: libc::c_int,
+	pub modes: *mut DrmModeModeInfo,
+	pub count_props: libc::c_int,
+	pub props: *mut libc::uint32_t,
+	pub prop_values: *mut libc::uint64_t,
+	pub count_encoders: libc::c_int,
+	pub encoders: *mut libc::uint32_t,
+}
+
+#[repr(C)]
+pub struct DrmModeEncoder {
+	pub encoder_id: libc::uint32_t,
+	pub encoder_type: libc::uint32_t,
+	pub crtc_id: libc::uint32_t,
+	pub possible_crtcs: libc::uint32_t,
+	pub possible_clones: libc::uint32_t,
+}
+
+#[repr(C)]
+pub struct DrmModeCrtc {
+	pub crtc_id: libc::uint32_t,
+	pub buffer_id: libc::uint32_t,
+	pub x: libc::uint32_t,
+	pub y: libc::uint32_t,
+	pub width: libc::uint32_t,
+	pub height: libc::uint32_t,
+	pub mode_valid: libc::c_int,
+	pub mode: DrmModeModeInfo,
+	pub gamma_size: libc::c_int,
+}
+
+#[repr(C)]
+pub struct DrmModeProperty {
+	pub prop_id: libc::uint32_t,
+	pub flags: libc::uint32_t,
+	pub name: [libc::c_char; DRM_PROP_NAME_LEN],
+	pub count_values: libc::c_int,
+	pub values: *mut libc::uint64_t,
+	pub count_enums: libc::c_int,
+	pub enums: *mut libc::c_void,
+	pub count_blobs: libc::c_int,
+	pub blob_ids: *mut libc::uint32_t,
+}
+
+#[repr(C)]
+pub struct DrmModePropertyBlob {
+	pub id: libc::uint32_t,
+	pub length: libc::uint32_t,
+	pub data: *mut libc::c_void,
+}
+
+pub type DrmPageFlipHandler = extern "C" fn(
+	fd: libc::c_int, sequence: libc::c_uint,
+	tv_sec: libc::c_uint, tv_usec: libc::c_uint,
+	user_data: *mut libc::c_void
+);
+
+#[repr(C)]
+pub struct DrmEventContext {
+	pub version: libc::c_int,
+	pub vblank_handler: Option<DrmPageFlipHandler>,
+	pub page_flip_handler: Option<DrmPageFlipHandler>,
+}
+
+shared_library!(LibDrm,
+	pub fn drmModeGetResources(fd: libc::c_int) -> *mut DrmModeRes,
+	pub fn drmModeFreeResources(ptr: *mut DrmModeRes),
+	pub fn drmModeGetConnector(fd: libc::c_int, connector_id: libc::uint32_t) -> *mut DrmModeConnector,
+	pub fn drmModeFreeConnector(ptr: *mut DrmModeConnector),
+	pub fn drmModeGetEncoder(fd: libc::c_int, encoder_id: libc::uint32_t) -> *mut DrmModeEncoder,
+	pub fn drmModeFreeEncoder(ptr: *mut DrmModeEncoder),
+	pub fn drmModeGetCrtc(fd: libc::c_int, crtc_id: libc::uint32_t) -> *mut DrmModeCrtc,
+	pub fn drmModeFreeCrtc(ptr: *mut DrmModeCrtc),
+	pub fn drmModeSetCrtc(
+		fd: libc::c_int, crtc_id: libc::uint32_t, buffer_id: libc::uint32_t,
+		x: libc::uint32_t, y: libc::uint32_t,
+		connectors: *const libc::uint32_t, count: libc::c_int,
+		mode: *const DrmModeModeInfo
+	) -> libc::c_int,
+	pub fn drmModeAddFB(
+		fd: libc::c_int, width: libc::uint32_t, height: libc::uint32_t,
+		depth: libc::uint8_t, bpp: libc::uint8_t, pitch: libc::uint32_t,
+		bo_handle: libc::uint32_t, buf_id: *mut libc::uint32_t
+	) -> libc::c_int,
+	pub fn drmModeRmFB(fd: libc::c_int, buffer_id: libc::uint32_t) -> libc::c_int,
+	pub fn drmModePageFlip(
+		fd: libc::c_int, crtc_id: libc::uint32_t, fb_id: libc::uint32_t,
+		flags: libc::uint32_t, user_data: *mut libc::c_void
+	) -> libc::c_int,
+	pub fn drmHandleEvent(fd: libc::c_int, context: *mut DrmEventContext) -> libc::c_int,
+	pub fn drmModeGetProperty(fd: libc::c_int, property_id: libc::uint32_t) -> *mut DrmModeProperty,
+	pub fn drmModeFreeProperty(ptr: *mut DrmModeProperty),
+	pub fn drmModeGetPropertyBlob(fd: libc::c_int, blob_id: libc::uint32_t) -> *mut DrmModePropertyBlob,
+	pub fn drmModeFreePropertyBlob(ptr: *mut DrmModePropertyBlob),
 );
 
+pub type GbmDevice = libc::c_void;
+pub type GbmSurface = libc::c_void;
+pub type GbmBo = libc::c_void;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct GbmBoHandle {
+	pub u64: libc::uint64_t,
+}
+
+pub const GBM_FORMAT_XRGB8888: libc::uint32_t = 0x34325258;
+pub const GBM_FORMAT_ARGB8888: libc::uint32_t = 0x34325241;
+pub const GBM_BO_USE_SCANOUT: libc::uint32_t = 1 << 0;
+pub const GBM_BO_USE_RENDERING: libc::uint32_t = 1 << 2;
+
+shared_library!(LibGbm,
+	pub fn gbm_create_device(fd: libc::c_int) -> *mut GbmDevice,
+	pub fn gbm_device_destroy(gbm: *mut GbmDevice),
+	pub fn gbm_surface_create(
+		gbm: *mut GbmDevice, width: libc::uint32_t, height: libc::uint32_t,
+		format: libc::uint32_t, flags: libc::uint32_t
+	) -> *mut GbmSurface,
+	pub fn gbm_surface_destroy(surface: *mut GbmSurface),
+	pub fn gbm_surface_lock_front_buffer(surface: *mut GbmSurface) -> *mut GbmBo,
+	pub fn gbm_surface_release_buffer(surface: *mut GbmSurface, bo: *mut GbmBo),
+	pub fn gbm_bo_get_handle(bo: *mut GbmBo) -> GbmBoHandle,
+	pub fn gbm_bo_get_stride(bo: *mut GbmBo) -> libc::uint32_t,
+);
+
+/// Multimedia abstraction layer of the VideoCore (libmmal_core.so).
+#[cfg(feature = "camera")]
+pub mod mmal {
+	use libc;
+
+	pub type MmalStatus = libc::uint32_t;
+	pub type MmalFourcc = libc::uint32_t;
+	pub type MmalQueue = libc::c_void;
+	pub type MmalPortBhCb = extern "C" fn(port: *mut MmalPort, buffer: *mut MmalBufferHeader);
+
+	pub const MMAL_SUCCESS: MmalStatus = 0;
+	pub const MMAL_ENCODING_OPAQUE: MmalFourcc = 0x5651504F; // 'OPQV'
+	pub const MMAL_COMPONENT_DEFAULT_CAMERA: &'static [u8] = b"vc.ril.camera\0";
+
+	pub const MMAL_PARAMETER_GROUP_COMMON: libc::uint32_t = 0 << 16;
+	pub const MMAL_PARAMETER_GROUP_CAMERA: libc::uint32_t = 1 << 16;
+	pub const MMAL_PARAMETER_ZERO_COPY: libc::uint32_t = MMAL_PARAMETER_GROUP_COMMON + 4;
+	pub const MMAL_PARAMETER_CAMERA_NUM: libc::uint32_t = MMAL_PARAMETER_GROUP_CAMERA + 16;
+	pub const MMAL_PARAMETER_CAMERA_CONFIG: libc::uint32_t = MMAL_PARAMETER_GROUP_CAMERA + 21;
+	pub const MMAL_PARAM_TIMESTAMP_MODE_RESET_STC: libc::uint32_t = 2;
+
+	#[repr(C)]
+	pub struct MmalRect {
+		pub x: libc::int32_t,
+		pub y: libc::int32_t,
+		pub width: libc::int32_t,
+		pub height: libc::int32_t,
+	}
+	#[repr(C)]
+	pub struct MmalRational {
+		pub num: libc::int32_t,
+		pub den: libc::int32_t,
+	}
+	#[repr(C)]
+	pub struct MmalVideoFormat {
+		pub width: libc::uint32_t,
+		pub height: libc::uint32_t,
+		pub crop: MmalRect,
+		pub frame_rate: MmalRational,
+		pub par: MmalRational,
+		pub color_space: MmalFourcc,
+	}
+	#[repr(C)]
+	pub struct MmalEsFormat {
+		pub es_type: libc::uint32_t,
+		pub encoding: MmalFourcc,
+		pub encoding_variant: MmalFourcc,
+		/// Union of the audio, video and subpicture formats. Only the video format is used.
+		pub es: *mut MmalVideoFormat,
+		pub bitrate: libc::uint32_t,
+		pub flags: libc::uint32_t,
+		pub extradata_size: libc::uint32_t,
+		pub extradata: *mut libc::uint8_t,
+	}
+	#[repr(C)]
+	pub struct MmalPort {
+		pub private: *mut libc::c_void,
+		pub name: *const libc::c_char,
+		pub port_type: libc::uint32_t,
+		pub index: libc::uint16_t,
+		pub index_all: libc::uint16_t,
+		pub is_enabled: libc::uint32_t,
+		pub format: *mut MmalEsFormat,
+		pub buffer_num_min: libc::uint32_t,
+		pub buffer_size_min: libc::uint32_t,
+		pub buffer_alignment_min: libc::uint32_t,
+		pub buffer_num_recommended: libc::uint32_t,
+		pub buffer_size_recommended: libc::uint32_t,
+		pub buffer_num: libc::uint32_t,
+		pub buffer_size: libc::uint32_t,
+		pub component: *mut MmalComponent,
+		pub userdata: *mut libc::c_void,
+		pub capabilities: libc::uint32_t,
+	}
+	#[repr(C)]
+	pub struct MmalComponent {
+		pub private: *mut libc::c_void,
+		pub userdata: *mut libc::c_void,
+		pub name: *const libc::c_char,
+		pub is_enabled: libc::uint32_t,
+		pub control: *mut MmalPort,
+		pub input_num: libc::uint32_t,
+		pub input: *mut *mut MmalPort,
+		pub output_num: libc::uint32_t,
+		pub output: *mut *mut MmalPort,
+		pub clock_num: libc::uint32_t,
+		pub clock: *mut *mut MmalPort,
+		pub port_num: libc::uint32_t,
+		pub port: *mut *mut MmalPort,
+		pub id: libc::uint32_t,
+	}
+	#[repr(C)]
+	pub struct MmalBufferHeader {
+		pub next: *mut MmalBufferHeader,
+		pub private: *mut libc::c_void,
+		pub cmd: libc::uint32_t,
+		pub data: *mut libc::uint8_t,
+		pub alloc_size: libc::uint32_t,
+		pub length: libc::uint32_t,
+		pub offset: libc::uint32_t,
+		pub flags: libc::uint32_t,
+		pub pts: libc::int64_t,
+		pub dts: libc::int64_t,
+		pub type_specific: *mut libc::c_void,
+		pub user_data: *mut libc::c_void,
+	}
+	#[repr(C)]
+	pub struct MmalPool {
+		pub queue: *mut MmalQueue,
+		pub headers_num: libc::uint32_t,
+		pub header: *mut *mut MmalBufferHeader,
+	}
+	#[repr(C)]
+	pub struct MmalParameterHeader {
+		pub id: libc::uint32_t,
+		pub size: libc::uint32_t,
+	}
+	#[repr(C)]
+	pub struct MmalParameterInt32 {
+		pub hdr: MmalParameterHeader,
+		pub value: libc::int32_t,
+	}
+	#[repr(C)]
+	pub struct MmalParameterCameraConfig {
+		pub hdr: MmalParameterHeader,
+		pub max_stills_w: libc::uint32_t,
+		pub max_stills_h: libc::uint32_t,
+		pub stills_yuv422: libc::uint32_t,
+		pub one_shot_stills: libc::uint32_t,
+		pub max_preview_video_w: libc::uint32_t,
+		pub max_preview_video_h: libc::uint32_t,
+		pub num_preview_video_frames: libc::uint32_t,
+		pub stills_capture_circular_buffer_height: libc::uint32_t,
+		pub fast_preview_resume: libc::uint32_t,
+		pub use_stc_timestamp: libc::uint32_t,
+	}
+
+	shared_library!(LibMmalCore,
+		pub fn mmal_component_create(name: *const libc::c_char, component: *mut *mut MmalComponent) -> MmalStatus,
+		pub fn mmal_component_destroy(component: *mut MmalComponent) -> MmalStatus,
+		pub fn mmal_component_enable(component: *mut MmalComponent) -> MmalStatus,
+		pub fn mmal_component_disable(component: *mut MmalComponent) -> MmalStatus,
+		pub fn mmal_port_format_commit(port: *mut MmalPort) -> MmalStatus,
+		pub fn mmal_port_enable(port: *mut MmalPort, cb: MmalPortBhCb) -> MmalStatus,
+		pub fn mmal_port_disable(port: *mut MmalPort) -> MmalStatus,
+		pub fn mmal_port_send_buffer(port: *mut MmalPort, buffer: *mut MmalBufferHeader) -> MmalStatus,
+		pub fn mmal_port_parameter_set(port: *mut MmalPort, param: *const MmalParameterHeader) -> MmalStatus,
+		pub fn mmal_port_pool_create(port: *mut MmalPort, headers: libc::c_uint, payload_size: libc::uint32_t) -> *mut MmalPool,
+		pub fn mmal_port_pool_destroy(port: *mut MmalPort, pool: *mut MmalPool),
+		pub fn mmal_queue_get(queue: *mut MmalQueue) -> *mut MmalBufferHeader,
+		pub fn mmal_buffer_header_release(header: *mut MmalBufferHeader),
+	);
+}
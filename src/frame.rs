@@ -0,0 +1,167 @@
+use glium::GlObject;
+use glium::backend::Facade;
+use glium::texture::{Texture2d, RawImage2d};
+
+use ffi;
+use error::Error;
+use image::ExternalTexture;
+use super::System;
+
+/// A source of video frames, e.g. the camera.
+pub trait FrameSource {
+	/// A frame of the source. Dropping it returns its buffer to the source.
+	type Frame: for<'a> VideoFrame<'a>;
+	/// The size of the frames.
+	fn dimensions(&self) -> (u32, u32);
+	/// The most recent frame if a new one arrived since the last call. Older frames are dropped.
+	fn next_frame(&mut self) -> Result<Option<Self::Frame>, Error>;
+}
+
+/// A frame of a `FrameSource` which can be shown by a texture.
+pub trait VideoFrame<'a> {
+	/// The texture showing the frame.
+	type Texture: FrameTexture;
+	/// Presentation time stamp in microseconds.
+	fn pts(&self) -> i64;
+	/// Show the frame by a new texture of the context of the facade. The frame is borrowed as long as the texture exists.
+	fn texture<F>(&'a self, system: &'a System, facade: &F) -> Result<Self::Texture, Error> where F: Facade;
+}
+
+/// A texture showing a frame, to be sampled in a shader.
+pub trait FrameTexture {
+	/// The GL texture name.
+	fn id(&self) -> u32;
+	/// The target to bind the texture to: GL_TEXTURE_2D for a `sampler2D` or GL_TEXTURE_EXTERNAL_OES for a `samplerExternalOES`.
+	fn target(&self) -> u32;
+}
+impl FrameTexture for Texture2d {
+	fn id(&self) -> u32 {
+		self.get_id()
+	}
+	fn target(&self) -> u32 {
+		ffi::GL_TEXTURE_2D
+	}
+}
+impl<'a> FrameTexture for ExternalTexture<'a> {
+	fn id(&self) -> u32 {
+		ExternalTexture::id(self)
+	}
+	fn target(&self) -> u32 {
+		ffi::GL_TEXTURE_EXTERNAL_OES
+	}
+}
+
+/// A frame source which generates RGBA frames on the CPU. Used to drive a pipeline without a camera, e.g. in tests.
+pub struct SyntheticSource<G> where G: FnMut(u64, &mut [u8]) {
+	/// The size of the frames.
+	dimensions: (u32, u32),
+	/// The index of the next frame.
+	index: u64,
+	/// Frames per second, used for the time stamps.
+	frame_rate: u32,
+	/// Fills the pixels of a frame given its index.
+	generate: G,
+}
+impl<G> SyntheticSource<G> where G: FnMut(u64, &mut [u8]) {
+	/// Create a source of frames of the size given at 30 frames per second.
+	/// The generator fills the RGBA pixels of each frame, row by row from the bottom as OpenGL expects.
+	pub fn new(dimensions: (u32, u32), generate: G) -> Self {
+		SyntheticSource {
+			dimensions: dimensions,
+			index: 0,
+			frame_rate: 30,
+			generate: generate,
+		}
+	}
+	/// Set the frames per second the time stamps of the frames are computed from.
+	pub fn with_frame_rate(mut self, frame_rate: u32) -> Self {
+		self.frame_rate = frame_rate;
+		self
+	}
+}
+impl<G> FrameSource for SyntheticSource<G> where G: FnMut(u64, &mut [u8]) {
+	type Frame = SyntheticFrame;
+	fn dimensions(&self) -> (u32, u32) {
+		self.dimensions
+	}
+	/// Generates a new frame on every call.
+	fn next_frame(&mut self) -> Result<Option<SyntheticFrame>, Error> {
+		let (width, height) = self.dimensions;
+		let mut pixels = vec![0u8; (width * height * 4) as usize];
+		(self.generate)(self.index, &mut pixels);
+		let frame = SyntheticFrame {
+			index: self.index,
+			pts: (self.index * 1000000 / self.frame_rate.max(1) as u64) as i64,
+			dimensions: self.dimensions,
+			pixels: pixels,
+		};
+		self.index += 1;
+		Ok(Some(frame))
+	}
+}
+
+/// A frame of a `SyntheticSource`.
+pub struct SyntheticFrame {
+	/// The index of the frame, starting at 0.
+	pub index: u64,
+	/// Presentation time stamp in microseconds, starting at 0.
+	pub pts: i64,
+	/// The size of the frame.
+	pub dimensions: (u32, u32),
+	/// RGBA pixels, row by row from the bottom.
+	pub pixels: Vec<u8>,
+}
+impl<'a> VideoFrame<'a> for SyntheticFrame {
+	type Texture = Texture2d;
+	fn pts(&self) -> i64 {
+		self.pts
+	}
+	/// Uploads the frame into a new texture. A glium texture, so it can be used as uniform as well.
+	fn texture<F>(&'a self, _: &'a System, facade: &F) -> Result<Texture2d, Error> where F: Facade {
+		let image = RawImage2d::from_raw_rgba(self.pixels.clone(), self.dimensions);
+		Texture2d::new(facade, image).map_err(|_| { Error::Fn("glTexImage2D") })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn synthetic_frames() {
+		let mut source = SyntheticSource::new((2, 1), |index, pixels| {
+			for pixel in pixels.chunks_mut(4) {
+				pixel.copy_from_slice(&[index as u8, 0, 0, 255]);
+			}
+		});
+		assert_eq!(source.dimensions(), (2, 1));
+		for index in 0..3 {
+			let frame = source.next_frame().unwrap().unwrap();
+			assert_eq!(frame.index, index);
+			assert_eq!(frame.dimensions, (2, 1));
+			assert_eq!(frame.pixels, vec![index as u8, 0, 0, 255, index as u8, 0, 0, 255]);
+		}
+	}
+
+	// Reads frames like a pipeline which works with any source.
+	fn read_frames<S>(source: &mut S, count: usize) -> Vec<(i64, S::Frame)> where S: FrameSource {
+		let mut frames = Vec::new();
+		while frames.len() < count {
+			match source.next_frame().unwrap() {
+				Some(frame) => frames.push((frame.pts(), frame)),
+				None => {},
+			}
+		}
+		frames
+	}
+
+	#[test]
+	fn generic_source() {
+		let mut source = SyntheticSource::new((4, 4), |_, _| {}).with_frame_rate(25);
+		let frames = read_frames(&mut source, 3);
+		assert_eq!(frames.iter().map(|&(pts, _)| { pts }).collect::<Vec<_>>(), vec![0, 40000, 80000]);
+		assert!(frames.iter().all(|&(_, ref frame)| { frame.pixels.len() == 4 * 4 * 4 }));
+		// Time stamps go on with the next frames.
+		assert_eq!(read_frames(&mut source, 1)[0].0, 120000);
+	}
+}
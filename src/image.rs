@@ -0,0 +1,260 @@
+use std::ffi::CStr;
+use std::mem;
+use std::ops::Deref;
+use std::os::unix::io::RawFd;
+use std::rc::Rc;
+
+use libc;
+use glium::backend::{Context, Facade};
+use glium::texture::{Texture2d, UncompressedFloatFormat, MipmapsOption, Dimensions};
+
+use ffi;
+use error::{Error, gl_error};
+use super::System;
+
+/// A buffer an EGL image can be created from.
+#[derive(Clone, Copy, Debug)]
+pub enum ImageSource {
+	/// A dispmanx resource (EGL_IMAGE_BRCM_DISPMANX). Only supported on the dispmanx platform.
+	DispmanxResource {
+		handle: u32,
+		width: u32,
+		height: u32,
+	},
+	/// A multimedia image, e.g. an opaque buffer of the VPU (EGL_IMAGE_BRCM_MULTIMEDIA). Only supported on the dispmanx platform.
+	Multimedia {
+		buffer: *mut libc::c_void,
+		width: u32,
+		height: u32,
+	},
+	/// A single plane dma-buf (EGL_EXT_image_dma_buf_import), e.g. a frame decoded by a V4L2 device.
+	DmaBuf {
+		fd: RawFd,
+		/// DRM fourcc code of the pixel format.
+		fourcc: u32,
+		width: u32,
+		height: u32,
+		/// Offset of the first pixel in bytes.
+		offset: u32,
+		/// Bytes per row.
+		pitch: u32,
+	},
+}
+impl ImageSource {
+	/// The size of the image.
+	pub fn dimensions(&self) -> (u32, u32) {
+		match *self {
+			ImageSource::DispmanxResource { width, height, .. } => (width, height),
+			ImageSource::Multimedia { width, height, .. } => (width, height),
+			ImageSource::DmaBuf { width, height, .. } => (width, height),
+		}
+	}
+}
+
+/// The functions of the image extensions, resolved once when the system is created. None if not provided.
+pub struct ImageFunctions {
+	/// eglCreateImageKHR
+	create_image: Option<ffi::EglCreateImageKhr>,
+	/// eglDestroyImageKHR
+	destroy_image: Option<ffi::EglDestroyImageKhr>,
+	/// glEGLImageTargetTexture2DOES
+	image_target_texture: Option<ffi::GlEglImageTargetTexture2dOes>,
+}
+impl ImageFunctions {
+	/// Resolve the functions with eglGetProcAddress.
+	pub unsafe fn load(lib_egl: &ffi::LibEGL) -> Self {
+		ImageFunctions {
+			create_image: proc_address(lib_egl, b"eglCreateImageKHR\0").map(|address| { mem::transmute(address) }),
+			destroy_image: proc_address(lib_egl, b"eglDestroyImageKHR\0").map(|address| { mem::transmute(address) }),
+			image_target_texture: proc_address(lib_egl, b"glEGLImageTargetTexture2DOES\0").map(|address| { mem::transmute(address) }),
+		}
+	}
+}
+
+/// An EGL image (EGLImageKHR) which can be bound to textures of any context of the system.
+pub struct EglImage<'a> {
+	/// The system the image belongs to.
+	system: &'a System,
+	/// The image.
+	image: ffi::EGLImageKHR,
+	/// The size of the image.
+	dimensions: (u32, u32),
+	/// eglDestroyImageKHR
+	destroy_image: ffi::EglDestroyImageKhr,
+}
+impl<'a> EglImage<'a> {
+	/// The size of the image.
+	pub fn dimensions(&self) -> (u32, u32) {
+		self.dimensions
+	}
+	/// The EGLImageKHR handle.
+	pub fn handle(&self) -> *const libc::c_void {
+		self.image
+	}
+}
+impl<'a> Drop for EglImage<'a> {
+	fn drop(&mut self) {
+		unsafe { (self.destroy_image)(self.system.egl_display, self.image); }
+	}
+}
+
+impl System {
+	/// Create an EGL image from a buffer. Requires EGL_KHR_image_base.
+	pub fn create_image(&self, source: &ImageSource) -> Result<EglImage, Error> {
+		if !self.has_egl_extension("EGL_KHR_image_base") { return Err(Error::Unsupported("EGL_KHR_image_base")); }
+		let none = [ffi::EGL_NONE as ffi::EGLint];
+		let (target, buffer, attributes) = match *source {
+			ImageSource::DispmanxResource { handle, .. } => {
+				try!(self.lib_bcm_host());
+				(ffi::EGL_IMAGE_BRCM_DISPMANX, handle as usize as ffi::EGLClientBuffer, none.to_vec())
+			},
+			ImageSource::Multimedia { buffer, .. } => {
+				try!(self.lib_bcm_host());
+				(ffi::EGL_IMAGE_BRCM_MULTIMEDIA, buffer as ffi::EGLClientBuffer, none.to_vec())
+			},
+			ImageSource::DmaBuf { fd, fourcc, width, height, offset, pitch } => {
+				if !self.has_egl_extension("EGL_EXT_image_dma_buf_import") { return Err(Error::Unsupported("EGL_EXT_image_dma_buf_import")); }
+				(ffi::EGL_LINUX_DMA_BUF_EXT, 0 as ffi::EGLClientBuffer, vec![
+					ffi::EGL_WIDTH as ffi::EGLint, width as ffi::EGLint,
+					ffi::EGL_HEIGHT as ffi::EGLint, height as ffi::EGLint,
+					ffi::EGL_LINUX_DRM_FOURCC_EXT as ffi::EGLint, fourcc as ffi::EGLint,
+					ffi::EGL_DMA_BUF_PLANE0_FD_EXT as ffi::EGLint, fd as ffi::EGLint,
+					ffi::EGL_DMA_BUF_PLANE0_OFFSET_EXT as ffi::EGLint, offset as ffi::EGLint,
+					ffi::EGL_DMA_BUF_PLANE0_PITCH_EXT as ffi::EGLint, pitch as ffi::EGLint,
+					ffi::EGL_NONE as ffi::EGLint,
+				])
+			},
+		};
+		let (create_image, destroy_image) = match (self.image_functions.create_image, self.image_functions.destroy_image) {
+			(Some(create_image), Some(destroy_image)) => (create_image, destroy_image),
+			_ => return Err(Error::Unsupported("eglCreateImageKHR")),
+		};
+		unsafe {
+			let image = create_image(self.egl_display, ffi::EGL_NO_CONTEXT, target, buffer, attributes.as_ptr());
+			if image == ffi::EGL_NO_IMAGE_KHR { return Err(Error::Fn("eglCreateImageKHR")); }
+			Ok(EglImage {
+				system: self,
+				image: image,
+				dimensions: source.dimensions(),
+				destroy_image: destroy_image,
+			})
+		}
+	}
+}
+
+/// A glium texture showing an EGL image without copying it (GL_OES_EGL_image).
+/// Changes of the buffer are visible in the texture.
+pub struct ImageTexture<'a> {
+	/// The texture. Dropped before the image.
+	texture: Texture2d,
+	/// The image.
+	image: EglImage<'a>,
+}
+impl<'a> ImageTexture<'a> {
+	/// Bind an image to a new texture of the context of the facade. Requires GL_OES_EGL_image.
+	pub fn new<F>(facade: &F, image: EglImage<'a>) -> Result<Self, Error> where F: Facade {
+		let id = try!(unsafe { bind_image(facade.get_context(), &image, ffi::GL_TEXTURE_2D, ffi::GL_TEXTURE_BINDING_2D, "GL_OES_EGL_image") });
+		let (width, height) = image.dimensions;
+		let texture = unsafe {
+			Texture2d::from_id(facade, UncompressedFloatFormat::U8U8U8U8, id, true, MipmapsOption::NoMipmap, Dimensions::Texture2d { width: width, height: height })
+		};
+		Ok(ImageTexture {
+			texture: texture,
+			image: image,
+		})
+	}
+	/// The image shown.
+	pub fn image(&self) -> &EglImage<'a> {
+		&self.image
+	}
+}
+impl<'a> Deref for ImageTexture<'a> {
+	type Target = Texture2d;
+	fn deref(&self) -> &Texture2d {
+		&self.texture
+	}
+}
+
+/// An external texture (GL_TEXTURE_EXTERNAL_OES) showing an EGL image, e.g. a YUV video frame.
+/// glium has no type for external textures. Use the id with raw GL calls and a `samplerExternalOES` in the shader.
+pub struct ExternalTexture<'a> {
+	/// The context the texture belongs to.
+	context: Rc<Context>,
+	/// GL texture name.
+	id: ffi::GLuint,
+	/// The image.
+	image: EglImage<'a>,
+}
+impl<'a> ExternalTexture<'a> {
+	/// Bind an image to a new external texture of the context of the facade. Requires GL_OES_EGL_image_external.
+	pub fn new<F>(facade: &F, image: EglImage<'a>) -> Result<Self, Error> where F: Facade {
+		let id = try!(unsafe { bind_image(facade.get_context(), &image, ffi::GL_TEXTURE_EXTERNAL_OES, ffi::GL_TEXTURE_BINDING_EXTERNAL_OES, "GL_OES_EGL_image_external") });
+		Ok(ExternalTexture {
+			context: facade.get_context().clone(),
+			id: id,
+			image: image,
+		})
+	}
+	/// The GL texture name. Bind it to GL_TEXTURE_EXTERNAL_OES.
+	pub fn id(&self) -> u32 {
+		self.id
+	}
+	/// The image shown.
+	pub fn image(&self) -> &EglImage<'a> {
+		&self.image
+	}
+}
+impl<'a> Drop for ExternalTexture<'a> {
+	fn drop(&mut self) {
+		let lib_glesv2 = &self.image.system.lib_glesv2;
+		let id = self.id;
+		unsafe { self.context.exec_in_context(|| { (lib_glesv2.glDeleteTextures)(1, &id as *const ffi::GLuint); }); }
+	}
+}
+
+// Load an EGL or GL extension function. None if it is not provided.
+unsafe fn proc_address(lib_egl: &ffi::LibEGL, name: &'static [u8]) -> Option<*const libc::c_void> {
+	let address = (lib_egl.eglGetProcAddress)(name.as_ptr() as *const libc::c_char);
+	if address.is_null() { None } else { Some(address) }
+}
+
+// Whether the context which is current supports a GL extension.
+unsafe fn has_gl_extension(lib_glesv2: &ffi::LibGLESv2, name: &str) -> bool {
+	let extensions = (lib_glesv2.glGetString)(ffi::GL_EXTENSIONS);
+	if extensions.is_null() { return false; }
+	CStr::from_ptr(extensions as *const libc::c_char).to_bytes().split(|b| { *b == b' ' }).any(|e| { e == name.as_bytes() })
+}
+
+// Create a texture in the context and bind the image to it. The texture binding of glium is restored afterwards.
+// Fails if the context does not support the extension given.
+unsafe fn bind_image(context: &Rc<Context>, image: &EglImage, target: ffi::GLenum, binding: ffi::GLenum, extension: &'static str) -> Result<ffi::GLuint, Error> {
+	let system = image.system;
+	let handle = image.image;
+	let image_target_texture = match system.image_functions.image_target_texture {
+		Some(image_target_texture) => image_target_texture,
+		None => return Err(Error::Unsupported("glEGLImageTargetTexture2DOES")),
+	};
+	context.exec_in_context(|| {
+		let lib_glesv2 = &system.lib_glesv2;
+		if !has_gl_extension(lib_glesv2, extension) { return Err(Error::Unsupported(extension)); }
+		let mut previous: ffi::GLint = 0;
+		(lib_glesv2.glGetIntegerv)(binding, &mut previous as *mut ffi::GLint);
+		let mut id: ffi::GLuint = 0;
+		(lib_glesv2.glGenTextures)(1, &mut id as *mut ffi::GLuint);
+		(lib_glesv2.glBindTexture)(target, id);
+		(lib_glesv2.glTexParameteri)(target, ffi::GL_TEXTURE_MIN_FILTER, ffi::GL_LINEAR as ffi::GLint);
+		(lib_glesv2.glTexParameteri)(target, ffi::GL_TEXTURE_MAG_FILTER, ffi::GL_LINEAR as ffi::GLint);
+		(lib_glesv2.glTexParameteri)(target, ffi::GL_TEXTURE_WRAP_S, ffi::GL_CLAMP_TO_EDGE as ffi::GLint);
+		(lib_glesv2.glTexParameteri)(target, ffi::GL_TEXTURE_WRAP_T, ffi::GL_CLAMP_TO_EDGE as ffi::GLint);
+		image_target_texture(target, handle);
+		let result = gl_error(lib_glesv2, "glEGLImageTargetTexture2DOES");
+		(lib_glesv2.glBindTexture)(target, previous as ffi::GLuint);
+		match result {
+			Ok(()) => Ok(id),
+			Err(e) => {
+				(lib_glesv2.glDeleteTextures)(1, &id as *const ffi::GLuint);
+				Err(e)
+			},
+		}
+	})
+}
@@ -0,0 +1,193 @@
+use std::ffi::CStr;
+use std::ops::Deref;
+use std::ptr;
+
+use libc;
+
+use ffi;
+use error::Error;
+use config::GlesVersion;
+use super::{System, Window};
+
+/// Attributes of an EGL frame buffer configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EglConfigInfo {
+	/// EGL_CONFIG_ID
+	pub id: i32,
+	/// Bits of the red channel.
+	pub red: u32,
+	/// Bits of the green channel.
+	pub green: u32,
+	/// Bits of the blue channel.
+	pub blue: u32,
+	/// Bits of the alpha channel.
+	pub alpha: u32,
+	/// Bits of the depth buffer.
+	pub depth: u32,
+	/// Bits of the stencil buffer.
+	pub stencil: u32,
+	/// Samples per pixel.
+	pub samples: u32,
+	/// Supported surface types (EGL_SURFACE_TYPE bit mask).
+	pub surface_type: u32,
+	/// Supported client APIs (EGL_RENDERABLE_TYPE bit mask).
+	pub renderable_type: u32,
+	/// Native visual id, e.g. the GBM format.
+	pub native_visual_id: i32,
+}
+
+/// Strings of the EGL implementation.
+#[derive(Clone, Debug)]
+pub struct EglInfo {
+	/// EGL_VENDOR
+	pub vendor: String,
+	/// EGL_VERSION
+	pub version: String,
+	/// EGL_CLIENT_APIS. Empty for EGL 1.1 implementations.
+	pub client_apis: String,
+	/// EGL_EXTENSIONS
+	pub extensions: Vec<String>,
+}
+impl EglInfo {
+	/// Whether the implementation supports an extension, e.g. "EGL_KHR_image_base".
+	pub fn has_extension(&self, name: &str) -> bool {
+		self.extensions.iter().any(|e| { e == name })
+	}
+	/// The version (major, minor) at the start of the version string.
+	pub fn version(&self) -> Option<(u32, u32)> {
+		parse_version(&self.version)
+	}
+	/// Whether the implementation supports a client API, e.g. "OpenGL_ES".
+	pub fn has_client_api(&self, name: &str) -> bool {
+		self.client_apis.split_whitespace().any(|a| { a == name })
+	}
+}
+
+/// Strings of the OpenGL ES implementation.
+#[derive(Clone, Debug)]
+pub struct GlInfo {
+	/// GL_VENDOR
+	pub vendor: String,
+	/// GL_RENDERER
+	pub renderer: String,
+	/// GL_VERSION
+	pub version: String,
+	/// GL_SHADING_LANGUAGE_VERSION
+	pub shading_language_version: String,
+	/// GL_EXTENSIONS
+	pub extensions: Vec<String>,
+}
+impl GlInfo {
+	/// Whether the implementation supports an extension, e.g. "GL_OES_texture_npot".
+	pub fn has_extension(&self, name: &str) -> bool {
+		self.extensions.iter().any(|e| { e == name })
+	}
+	/// The OpenGL ES version of the context.
+	pub fn gles_version(&self) -> Option<GlesVersion> {
+		GlesVersion::parse(&self.version)
+	}
+	/// The GLSL ES version (major, minor), e.g. (1, 0) for "OpenGL ES GLSL ES 1.00".
+	pub fn glsl_version(&self) -> Option<(u32, u32)> {
+		let prefix = "OpenGL ES GLSL ES ";
+		if !self.shading_language_version.starts_with(prefix) { return None; }
+		parse_version(&self.shading_language_version[prefix.len()..]).map(|(major, minor)| {
+			// The minor version is given with two digits (1.00, 3.10).
+			(major, if minor >= 10 { minor / 10 } else { minor })
+		})
+	}
+}
+
+// Parse "major.minor" at the start of a string.
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+	let mut numbers = version.split(|c: char| { !c.is_digit(10) });
+	match (numbers.next().and_then(|n| { n.parse().ok() }), numbers.next().and_then(|n| { n.parse().ok() })) {
+		(Some(major), Some(minor)) => Some((major, minor)),
+		_ => None,
+	}
+}
+
+impl System {
+	/// All frame buffer configurations of the EGL display.
+	pub fn egl_configs(&self) -> Result<Vec<EglConfigInfo>, Error> {
+		unsafe {
+			let mut count: ffi::EGLint = 0;
+			if (self.lib_egl.eglGetConfigs)(self.egl_display, ptr::null_mut(), 0, &mut count as *mut ffi::EGLint) == 0 { return Err(Error::Fn("eglGetConfigs")); }
+			let mut configs: Vec<ffi::EGLConfig> = vec![ptr::null(); count as usize];
+			if (self.lib_egl.eglGetConfigs)(self.egl_display, configs.as_mut_ptr(), count, &mut count as *mut ffi::EGLint) == 0 { return Err(Error::Fn("eglGetConfigs")); }
+			configs.truncate(count as usize);
+
+			let mut infos = Vec::with_capacity(configs.len());
+			for config in configs {
+				infos.push(EglConfigInfo {
+					id: try!(self.config_attrib(config, ffi::EGL_CONFIG_ID)),
+					red: try!(self.config_attrib(config, ffi::EGL_RED_SIZE)) as u32,
+					green: try!(self.config_attrib(config, ffi::EGL_GREEN_SIZE)) as u32,
+					blue: try!(self.config_attrib(config, ffi::EGL_BLUE_SIZE)) as u32,
+					alpha: try!(self.config_attrib(config, ffi::EGL_ALPHA_SIZE)) as u32,
+					depth: try!(self.config_attrib(config, ffi::EGL_DEPTH_SIZE)) as u32,
+					stencil: try!(self.config_attrib(config, ffi::EGL_STENCIL_SIZE)) as u32,
+					samples: try!(self.config_attrib(config, ffi::EGL_SAMPLES)) as u32,
+					surface_type: try!(self.config_attrib(config, ffi::EGL_SURFACE_TYPE)) as u32,
+					renderable_type: try!(self.config_attrib(config, ffi::EGL_RENDERABLE_TYPE)) as u32,
+					native_visual_id: try!(self.config_attrib(config, ffi::EGL_NATIVE_VISUAL_ID)),
+				});
+			}
+			Ok(infos)
+		}
+	}
+	/// The strings of the EGL implementation.
+	pub fn egl_info(&self) -> Result<EglInfo, Error> {
+		unsafe {
+			let extensions = try!(self.egl_string(ffi::EGL_EXTENSIONS, "eglQueryString(EGL_EXTENSIONS)"));
+			Ok(EglInfo {
+				vendor: try!(self.egl_string(ffi::EGL_VENDOR, "eglQueryString(EGL_VENDOR)")),
+				version: try!(self.egl_string(ffi::EGL_VERSION, "eglQueryString(EGL_VERSION)")),
+				client_apis: self.egl_string(ffi::EGL_CLIENT_APIS, "eglQueryString(EGL_CLIENT_APIS)").unwrap_or(String::new()),
+				extensions: extensions.split_whitespace().map(|e| { e.to_string() }).collect(),
+			})
+		}
+	}
+	/// Whether the EGL implementation supports an extension, e.g. "EGL_KHR_image_base".
+	pub fn has_egl_extension(&self, name: &str) -> bool {
+		self.egl_extensions.iter().any(|e| { e == name })
+	}
+	// Read an attribute of an EGL config.
+	unsafe fn config_attrib(&self, config: ffi::EGLConfig, attribute: ffi::GLenum) -> Result<ffi::EGLint, Error> {
+		let mut value: ffi::EGLint = 0;
+		if (self.lib_egl.eglGetConfigAttrib)(self.egl_display, config, attribute as ffi::EGLint, &mut value as *mut ffi::EGLint) == 0 { return Err(Error::Fn("eglGetConfigAttrib")); }
+		Ok(value)
+	}
+	// Query a string of the EGL display.
+	unsafe fn egl_string(&self, name: ffi::GLenum, fn_name: &'static str) -> Result<String, Error> {
+		let string = (self.lib_egl.eglQueryString)(self.egl_display, name as ffi::EGLint);
+		if string.is_null() { return Err(Error::Fn(fn_name)); }
+		Ok(CStr::from_ptr(string).to_string_lossy().into_owned())
+	}
+}
+
+impl<S> Window<S> where S: Deref<Target=System> {
+	/// The strings of the OpenGL ES implementation. The window must be current on the calling thread.
+	pub fn gl_info(&self) -> Result<GlInfo, Error> {
+		if unsafe { (self.system.lib_egl.eglGetCurrentContext)() } != self.egl_context { return Err(Error::Unsupported("the window is not current on this thread")); }
+		unsafe {
+			let extensions = try!(self.gl_string(ffi::GL_EXTENSIONS, "glGetString(GL_EXTENSIONS)"));
+			Ok(GlInfo {
+				vendor: try!(self.gl_string(ffi::GL_VENDOR, "glGetString(GL_VENDOR)")),
+				renderer: try!(self.gl_string(ffi::GL_RENDERER, "glGetString(GL_RENDERER)")),
+				version: try!(self.gl_string(ffi::GL_VERSION, "glGetString(GL_VERSION)")),
+				shading_language_version: try!(self.gl_string(ffi::GL_SHADING_LANGUAGE_VERSION, "glGetString(GL_SHADING_LANGUAGE_VERSION)")),
+				extensions: extensions.split_whitespace().map(|e| { e.to_string() }).collect(),
+			})
+		}
+	}
+	/// Whether the OpenGL ES implementation supports an extension. The window must be current on the calling thread.
+	pub fn has_extension(&self, name: &str) -> Result<bool, Error> {
+		self.gl_info().map(|info| { info.has_extension(name) })
+	}
+	// Query a string of the current GL context.
+	unsafe fn gl_string(&self, name: ffi::GLenum, fn_name: &'static str) -> Result<String, Error> {
+		let string = (self.system.lib_glesv2.glGetString)(name);
+		if string.is_null() { return Err(Error::Fn(fn_name)); }
+		Ok(CStr::from_ptr(string as *const libc::c_char).to_string_lossy().into_owned())
+	}
+}
@@ -0,0 +1,480 @@
+use std::cell::Cell;
+use std::ffi::CStr;
+use std::fs::{self, File, OpenOptions};
+use std::mem;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::sync::Mutex;
+
+use libc;
+
+use ffi;
+use error::Error;
+use config::{Display, KmsConfig};
+
+/// Names of the DRM connector types, indexed by the connector type.
+const CONNECTOR_TYPE_NAMES: [&'static str; 21] = [
+	"Unknown", "VGA", "DVI-I", "DVI-D", "DVI-A", "Composite", "SVIDEO", "LVDS", "Component", "DIN",
+	"DP", "HDMI-A", "HDMI-B", "TV", "eDP", "Virtual", "DSI", "DPI", "Writeback", "SPI", "USB",
+];
+
+/// Connector types which are used for a display.
+fn connector_types(display: Display) -> &'static [libc::uint32_t] {
+	match display {
+		// HDMI-A, HDMI-B
+		Display::Hdmi => &[11, 12],
+		// Composite, SVIDEO, TV
+		Display::Analog => &[5, 6, 13],
+		// LVDS, eDP, DSI, DPI
+		Display::Lcd => &[7, 14, 16, 17],
+	}
+}
+
+/// Name of a connector (e.g. "HDMI-A-1").
+fn connector_name(connector: &ffi::DrmModeConnector) -> String {
+	let type_name = CONNECTOR_TYPE_NAMES.get(connector.connector_type as usize).unwrap_or(&"Unknown");
+	format!("{}-{}", type_name, connector.connector_type_id)
+}
+
+/// A connector together with the CRTC driving it and the mode to use.
+#[derive(Copy, Clone)]
+pub struct Output {
+	/// Connector id.
+	pub connector_id: libc::uint32_t,
+	/// CRTC id.
+	pub crtc_id: libc::uint32_t,
+	/// The mode.
+	pub mode: ffi::DrmModeModeInfo,
+}
+impl Output {
+	/// The size of the mode.
+	pub fn size(&self) -> (u32, u32) {
+		(self.mode.hdisplay as u32, self.mode.vdisplay as u32)
+	}
+}
+
+/// A DRM device with mode setting support and the GBM device created on it.
+pub struct Device {
+	/// DRM library.
+	lib_drm: ffi::LibDrm,
+	/// GBM library.
+	lib_gbm: ffi::LibGbm,
+	/// Path of the device.
+	path: PathBuf,
+	/// The opened device file.
+	file: File,
+	/// Connector name overriding the display mapping.
+	connector: Option<String>,
+	/// GBM device.
+	gbm_device: *mut ffi::GbmDevice,
+	/// Serializes reading the page flip events of all surfaces from the file descriptor.
+	events: Mutex<()>,
+}
+impl Device {
+	/// Open the DRM device of the configuration (or the first one supporting mode setting) and create a GBM device on it.
+	/// Uses the file given instead if any, e.g. a device of the vkms driver opened by the caller.
+	pub fn open(config: &KmsConfig, file: Option<File>) -> Result<Self, Error> {
+		let lib_drm = try!(
+			ffi::LibDrm::open(&config.lib_dir.join("libdrm.so.2")).map_err(|e| { Error::Sl(e) })
+		);
+		let lib_gbm = try!(
+			ffi::LibGbm::open(&config.lib_dir.join("libgbm.so.1")).map_err(|e| { Error::Sl(e) })
+		);
+
+		let (path, file) = match (file, config.device.as_ref()) {
+			(Some(file), path) => {
+				try!(check_device(&lib_drm, &file));
+				(path.cloned().unwrap_or_default(), file)
+			},
+			(None, Some(path)) => (path.clone(), try!(open_device(&lib_drm, path))),
+			(None, None) => {
+				let mut paths: Vec<PathBuf> = try!(fs::read_dir("/dev/dri").map_err(|e| { Error::Io(e) }))
+					.filter_map(|entry| { entry.ok() })
+					.map(|entry| { entry.path() })
+					.filter(|path| { path.file_name().and_then(|n| { n.to_str() }).map_or(false, |n| { n.starts_with("card") }) })
+					.collect();
+				paths.sort();
+				let mut found = None;
+				for path in paths {
+					match open_device(&lib_drm, &path) {
+						Ok(file) => { found = Some((path, file)); break; },
+						Err(_) => {},
+					}
+				}
+				match found {
+					Some(found) => found,
+					None => return Err(Error::Unsupported("no DRM device with mode setting support")),
+				}
+			},
+		};
+
+		let gbm_device = unsafe { (lib_gbm.gbm_create_device)(file.as_raw_fd()) };
+		if gbm_device.is_null() { return Err(Error::Fn("gbm_create_device")); }
+
+		Ok(Device {
+			lib_drm: lib_drm,
+			lib_gbm: lib_gbm,
+			path: path,
+			file: file,
+			connector: config.connector.clone(),
+			gbm_device: gbm_device,
+			events: Mutex::new(()),
+		})
+	}
+	/// The path of the DRM device in use.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+	/// The native display to get the EGL display from.
+	pub fn native_display(&self) -> ffi::EGLNativeDisplayType {
+		self.gbm_device as ffi::EGLNativeDisplayType
+	}
+	/// The file descriptor of the device.
+	fn fd(&self) -> libc::c_int {
+		self.file.as_raw_fd()
+	}
+	/// Find the connector of a display and call the function with it.
+	unsafe fn with_connector<T, F>(&self, display: Display, mut f: F) -> Result<T, Error>
+		where F: FnMut(&ffi::DrmModeRes, &ffi::DrmModeConnector) -> Result<T, Error>
+	{
+		let resources = (self.lib_drm.drmModeGetResources)(self.fd());
+		if resources.is_null() { return Err(Error::Fn("drmModeGetResources")); }
+		let connector_ids = slice::from_raw_parts((*resources).connectors, (*resources).count_connectors as usize);
+		let mut result = Err(Error::Unsupported("no connected connector for the display"));
+		for connector_id in connector_ids {
+			let connector = (self.lib_drm.drmModeGetConnector)(self.fd(), *connector_id);
+			if connector.is_null() { continue; }
+			let matches = (*connector).connection == ffi::DRM_MODE_CONNECTED && (*connector).count_modes > 0 && match self.connector {
+				Some(ref name) => *name == connector_name(&*connector),
+				None => connector_types(display).contains(&(*connector).connector_type),
+			};
+			if matches {
+				result = f(&*resources, &*connector);
+			}
+			(self.lib_drm.drmModeFreeConnector)(connector);
+			if matches { break; }
+		}
+		(self.lib_drm.drmModeFreeResources)(resources);
+		result
+	}
+	/// Find the output of a display. Uses the mode of the size given, or the preferred mode.
+	pub unsafe fn output(&self, display: Display, size: Option<(u32, u32)>) -> Result<Output, Error> {
+		self.with_connector(display, |resources, connector| {
+			let modes = slice::from_raw_parts(connector.modes, connector.count_modes as usize);
+			let mode = match size {
+				Some((width, height)) => match modes.iter().find(|m| { m.hdisplay as u32 == width && m.vdisplay as u32 == height }) {
+					Some(mode) => *mode,
+					None => return Err(Error::Unsupported("no mode of the requested surface size")),
+				},
+				None => *modes.iter().find(|m| { m.mode_type & ffi::DRM_MODE_TYPE_PREFERRED != 0 }).unwrap_or(&modes[0]),
+			};
+			let crtc_id = try!(self.crtc(resources, connector));
+			Ok(Output {
+				connector_id: connector.connector_id,
+				crtc_id: crtc_id,
+				mode: mode,
+			})
+		})
+	}
+	/// Find a CRTC for a connector. Prefers the one currently driving it.
+	unsafe fn crtc(&self, resources: &ffi::DrmModeRes, connector: &ffi::DrmModeConnector) -> Result<libc::uint32_t, Error> {
+		if connector.encoder_id != 0 {
+			let encoder = (self.lib_drm.drmModeGetEncoder)(self.fd(), connector.encoder_id);
+			if !encoder.is_null() {
+				let crtc_id = (*encoder).crtc_id;
+				(self.lib_drm.drmModeFreeEncoder)(encoder);
+				if crtc_id != 0 { return Ok(crtc_id); }
+			}
+		}
+		let crtc_ids = slice::from_raw_parts(resources.crtcs, resources.count_crtcs as usize);
+		let encoder_ids = slice::from_raw_parts(connector.encoders, connector.count_encoders as usize);
+		for encoder_id in encoder_ids {
+			let encoder = (self.lib_drm.drmModeGetEncoder)(self.fd(), *encoder_id);
+			if encoder.is_null() { continue; }
+			let possible_crtcs = (*encoder).possible_crtcs;
+			(self.lib_drm.drmModeFreeEncoder)(encoder);
+			match crtc_ids.iter().enumerate().find(|&(i, _)| { possible_crtcs & (1 << i) != 0 }) {
+				Some((_, crtc_id)) => return Ok(*crtc_id),
+				None => {},
+			}
+		}
+		Err(Error::Unsupported("no CRTC for the connector"))
+	}
+	/// Read the EDID blob of the connector of a display.
+	pub unsafe fn edid(&self, display: Display) -> Result<Vec<u8>, Error> {
+		self.with_connector(display, |_, connector| {
+			let props = slice::from_raw_parts(connector.props, connector.count_props as usize);
+			let values = slice::from_raw_parts(connector.prop_values, connector.count_props as usize);
+			for (prop_id, value) in props.iter().zip(values.iter()) {
+				let prop = (self.lib_drm.drmModeGetProperty)(self.fd(), *prop_id);
+				if prop.is_null() { continue; }
+				let is_edid = CStr::from_ptr((*prop).name.as_ptr()).to_bytes() == b"EDID";
+				(self.lib_drm.drmModeFreeProperty)(prop);
+				if !is_edid { continue; }
+				let blob = (self.lib_drm.drmModeGetPropertyBlob)(self.fd(), *value as libc::uint32_t);
+				if blob.is_null() { return Err(Error::Fn("drmModeGetPropertyBlob")); }
+				let data = slice::from_raw_parts((*blob).data as *const u8, (*blob).length as usize).to_vec();
+				(self.lib_drm.drmModeFreePropertyBlob)(blob);
+				return Ok(data);
+			}
+			Err(Error::Unsupported("the connector provides no EDID"))
+		})
+	}
+}
+impl Drop for Device {
+	fn drop(&mut self) {
+		unsafe { (self.lib_gbm.gbm_device_destroy)(self.gbm_device); }
+	}
+}
+
+/// Open a DRM device and check whether it supports mode setting.
+fn open_device(lib_drm: &ffi::LibDrm, path: &Path) -> Result<File, Error> {
+	let file = try!(OpenOptions::new().read(true).write(true).open(path).map_err(|e| { Error::Io(e) }));
+	try!(check_device(lib_drm, &file));
+	Ok(file)
+}
+
+/// Check whether an opened DRM device supports mode setting.
+fn check_device(lib_drm: &ffi::LibDrm, file: &File) -> Result<(), Error> {
+	let resources = unsafe { (lib_drm.drmModeGetResources)(file.as_raw_fd()) };
+	if resources.is_null() { return Err(Error::Fn("drmModeGetResources")); }
+	let has_connectors = unsafe { (*resources).count_connectors > 0 };
+	unsafe { (lib_drm.drmModeFreeResources)(resources); }
+	if !has_connectors { return Err(Error::Unsupported("the DRM device has no connectors")); }
+	Ok(())
+}
+
+/// Whether GBM surfaces of a pixel format can be scanned out.
+pub fn is_scanout_format(format: libc::uint32_t) -> bool {
+	format == ffi::GBM_FORMAT_XRGB8888 || format == ffi::GBM_FORMAT_ARGB8888
+}
+
+// Called by drmHandleEvent when a page flip has completed.
+extern "C" fn page_flip_handler(_fd: libc::c_int, _sequence: libc::c_uint, _tv_sec: libc::c_uint, _tv_usec: libc::c_uint, user_data: *mut libc::c_void) {
+	unsafe { (*(user_data as *const Cell<bool>)).set(true); }
+}
+
+/// A GBM surface which is scanned out by a CRTC.
+pub struct Surface {
+	/// GBM surface.
+	gbm_surface: *mut ffi::GbmSurface,
+	/// The pixel format of the surface.
+	format: libc::uint32_t,
+	/// The output the surface is shown on.
+	output: Output,
+	/// The CRTC state before the surface was shown. Restored on destruction.
+	saved_crtc: *mut ffi::DrmModeCrtc,
+	/// The buffer object currently scanned out.
+	bo: Cell<*mut ffi::GbmBo>,
+	/// The framebuffer of the buffer object currently scanned out.
+	fb: Cell<libc::uint32_t>,
+	/// The buffer object of a page flip which did not complete yet. Null if none.
+	pending_bo: Cell<*mut ffi::GbmBo>,
+	/// The framebuffer of the pending page flip.
+	pending_fb: Cell<libc::uint32_t>,
+	/// Set by the page flip handler. Boxed, so that it stays in place while a flip is pending and the surface is moved.
+	flipped: Box<Cell<bool>>,
+}
+impl Surface {
+	/// Create a GBM surface for an output. The format must be the native visual id of the EGL config used.
+	pub unsafe fn new(device: &Device, output: Output, format: libc::uint32_t) -> Result<Self, Error> {
+		if !is_scanout_format(format) { return Err(Error::Unsupported("the pixel format can not be scanned out")); }
+		let (width, height) = output.size();
+		let gbm_surface = (device.lib_gbm.gbm_surface_create)(
+			device.gbm_device,
+			width, height,
+			format,
+			ffi::GBM_BO_USE_SCANOUT | ffi::GBM_BO_USE_RENDERING
+		);
+		if gbm_surface.is_null() { return Err(Error::Fn("gbm_surface_create")); }
+		Ok(Surface {
+			gbm_surface: gbm_surface,
+			format: format,
+			output: output,
+			saved_crtc: (device.lib_drm.drmModeGetCrtc)(device.fd(), output.crtc_id),
+			bo: Cell::new(0 as *mut ffi::GbmBo),
+			fb: Cell::new(0),
+			pending_bo: Cell::new(0 as *mut ffi::GbmBo),
+			pending_fb: Cell::new(0),
+			flipped: Box::new(Cell::new(false)),
+		})
+	}
+	/// The native window to create the EGL surface from.
+	pub fn native_window(&self) -> ffi::EGLNativeWindowType {
+		self.gbm_surface as ffi::EGLNativeWindowType
+	}
+	/// The size of the surface.
+	pub fn dimensions(&self) -> (u32, u32) {
+		self.output.size()
+	}
+	/// Show the front buffer after eglSwapBuffers. Sets the mode on the first call, page flips and waits for the flip afterwards.
+	/// Surfaces may be presented on different threads at the same time.
+	pub unsafe fn present(&self, device: &Device) -> Result<(), Error> {
+		// A flip whose wait failed before must complete before the next one.
+		try!(self.wait_for_flip(device));
+		let bo = (device.lib_gbm.gbm_surface_lock_front_buffer)(self.gbm_surface);
+		if bo.is_null() { return Err(Error::Fn("gbm_surface_lock_front_buffer")); }
+		let (width, height) = self.dimensions();
+		let depth = if self.format == ffi::GBM_FORMAT_ARGB8888 { 32 } else { 24 };
+		let mut fb: libc::uint32_t = 0;
+		if (device.lib_drm.drmModeAddFB)(
+			device.fd(),
+			width, height,
+			depth, 32,
+			(device.lib_gbm.gbm_bo_get_stride)(bo),
+			(device.lib_gbm.gbm_bo_get_handle)(bo).u64 as libc::uint32_t,
+			&mut fb as *mut libc::uint32_t
+		) != 0 {
+			(device.lib_gbm.gbm_surface_release_buffer)(self.gbm_surface, bo);
+			return Err(Error::Fn("drmModeAddFB"));
+		}
+
+		if self.bo.get().is_null() {
+			if (device.lib_drm.drmModeSetCrtc)(
+				device.fd(), self.output.crtc_id, fb,
+				0, 0,
+				&self.output.connector_id as *const libc::uint32_t, 1,
+				&self.output.mode as *const ffi::DrmModeModeInfo
+			) != 0 {
+				(device.lib_drm.drmModeRmFB)(device.fd(), fb);
+				(device.lib_gbm.gbm_surface_release_buffer)(self.gbm_surface, bo);
+				return Err(Error::Fn("drmModeSetCrtc"));
+			}
+			self.bo.set(bo);
+			self.fb.set(fb);
+			Ok(())
+		} else {
+			self.flipped.set(false);
+			if (device.lib_drm.drmModePageFlip)(
+				device.fd(), self.output.crtc_id, fb,
+				ffi::DRM_MODE_PAGE_FLIP_EVENT,
+				&*self.flipped as *const Cell<bool> as *mut libc::c_void
+			) != 0 {
+				(device.lib_drm.drmModeRmFB)(device.fd(), fb);
+				(device.lib_gbm.gbm_surface_release_buffer)(self.gbm_surface, bo);
+				return Err(Error::Fn("drmModePageFlip"));
+			}
+			self.pending_bo.set(bo);
+			self.pending_fb.set(fb);
+			self.wait_for_flip(device)
+		}
+	}
+	/// Wait until a pending page flip completes and release the buffer scanned out before. If waiting fails, the flip stays pending.
+	unsafe fn wait_for_flip(&self, device: &Device) -> Result<(), Error> {
+		if self.pending_bo.get().is_null() { return Ok(()); }
+		let mut event_context = ffi::DrmEventContext {
+			version: ffi::DRM_EVENT_CONTEXT_VERSION,
+			vblank_handler: None,
+			page_flip_handler: Some(page_flip_handler),
+		};
+		{
+			// Another thread may read the event of this flip. It is handled while the lock is held, so the flag is set then.
+			let _guard = device.events.lock().unwrap();
+			while !self.flipped.get() {
+				if (device.lib_drm.drmHandleEvent)(device.fd(), &mut event_context as *mut ffi::DrmEventContext) != 0 { return Err(Error::Fn("drmHandleEvent")); }
+			}
+		}
+		// The previous buffer is not scanned out anymore.
+		self.release(device);
+		self.bo.set(self.pending_bo.get());
+		self.fb.set(self.pending_fb.get());
+		self.pending_bo.set(0 as *mut ffi::GbmBo);
+		self.pending_fb.set(0);
+		Ok(())
+	}
+	/// Release the buffer object currently scanned out.
+	unsafe fn release(&self, device: &Device) {
+		if !self.bo.get().is_null() {
+			(device.lib_drm.drmModeRmFB)(device.fd(), self.fb.get());
+			(device.lib_gbm.gbm_surface_release_buffer)(self.gbm_surface, self.bo.get());
+			self.bo.set(0 as *mut ffi::GbmBo);
+			self.fb.set(0);
+		}
+	}
+	/// Restore the previous CRTC state and destroy the GBM surface. The EGL surface must have been destroyed before.
+	pub unsafe fn destroy(&mut self, device: &Device) {
+		if self.wait_for_flip(device).is_err() {
+			// The flip may still complete, so the flag must stay valid.
+			mem::forget(mem::replace(&mut self.flipped, Box::new(Cell::new(false))));
+		}
+		if !self.saved_crtc.is_null() {
+			let crtc = &*self.saved_crtc;
+			(device.lib_drm.drmModeSetCrtc)(
+				device.fd(), crtc.crtc_id, crtc.buffer_id,
+				crtc.x, crtc.y,
+				&self.output.connector_id as *const libc::uint32_t, 1,
+				&crtc.mode as *const ffi::DrmModeModeInfo
+			);
+			(device.lib_drm.drmModeFreeCrtc)(self.saved_crtc);
+			self.saved_crtc = 0 as *mut ffi::DrmModeCrtc;
+		}
+		self.release(device);
+		if !self.pending_bo.get().is_null() {
+			(device.lib_drm.drmModeRmFB)(device.fd(), self.pending_fb.get());
+			(device.lib_gbm.gbm_surface_release_buffer)(self.gbm_surface, self.pending_bo.get());
+			self.pending_bo.set(0 as *mut ffi::GbmBo);
+			self.pending_fb.set(0);
+		}
+		if !self.gbm_surface.is_null() {
+			(device.lib_gbm.gbm_surface_destroy)(self.gbm_surface);
+			self.gbm_surface = 0 as *mut ffi::GbmSurface;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+	use std::fs::{File, OpenOptions};
+	use std::path::PathBuf;
+
+	use glium::backend::Backend;
+
+	use super::*;
+	use error::Error;
+	use config::{KmsConfig, WindowConfig};
+	use {System, Window};
+
+	#[test]
+	fn scanout_formats() {
+		assert!(is_scanout_format(ffi::GBM_FORMAT_XRGB8888));
+		assert!(is_scanout_format(ffi::GBM_FORMAT_ARGB8888));
+		// RGB565
+		assert!(!is_scanout_format(0x36314752));
+	}
+
+	#[test]
+	fn reject_file_without_mode_setting() {
+		let file = File::open("/dev/null").unwrap();
+		match Device::open(&KmsConfig::default(), Some(file)) {
+			Err(Error::Fn("drmModeGetResources")) => {},
+			// libdrm or libgbm is not installed.
+			Err(Error::Sl(_)) => {},
+			Err(e) => panic!("unexpected error {}", e),
+			Ok(_) => panic!("/dev/null was accepted as DRM device"),
+		}
+	}
+
+	// Renders to a DRM device given by GLIUM_PIB_TEST_DRM_DEVICE, e.g. /dev/dri/card0 after `modprobe vkms`. Run with --ignored.
+	// The connector is given by GLIUM_PIB_TEST_DRM_CONNECTOR and defaults to the one of vkms.
+	#[test]
+	#[ignore]
+	fn render_to_device() {
+		let path = PathBuf::from(env::var_os("GLIUM_PIB_TEST_DRM_DEVICE").expect("GLIUM_PIB_TEST_DRM_DEVICE is not set"));
+		let connector = env::var("GLIUM_PIB_TEST_DRM_CONNECTOR").unwrap_or("Virtual-1".to_string());
+		let file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+		let system = System::new_kms(KmsConfig {
+			device: Some(path),
+			connector: Some(connector),
+			file: Some(file),
+			.. KmsConfig::default()
+		}).unwrap();
+		let window = Window::new(&system, &WindowConfig::default()).unwrap();
+		assert!(window.get_framebuffer_dimensions().0 > 0);
+		// The first swap sets the mode, the following ones page flip.
+		for _ in 0..3 {
+			window.swap_buffers().unwrap();
+		}
+	}
+}
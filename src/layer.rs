@@ -0,0 +1,203 @@
+use std::ops::Deref;
+
+use libc;
+
+use ffi;
+use error::Error;
+use config::{Display, Rect, FixedRect};
+use dispmanx;
+use super::System;
+
+/// Pixel format of a dispmanx resource.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResourceFormat {
+	/// 16 bits per pixel: 5 bits red, 6 bits green, 5 bits blue.
+	Rgb565,
+	/// 32 bits per pixel: 8 bits red, green, blue and alpha. The alpha channel blends the layer with the layers below.
+	Rgba32,
+	/// Planar YUV 4:2:0. The U and V planes of half the pitch and half the height follow the Y plane.
+	/// The height of each plane is rounded up to a multiple of 16 rows.
+	Yuv420,
+}
+impl ResourceFormat {
+	/// The VideoCore image type.
+	fn image_type(&self) -> ffi::VcImageType {
+		match *self {
+			ResourceFormat::Rgb565 => ffi::VC_IMAGE_RGB565,
+			ResourceFormat::Rgba32 => ffi::VC_IMAGE_RGBA32,
+			ResourceFormat::Yuv420 => ffi::VC_IMAGE_YUV420,
+		}
+	}
+	/// The minimal pitch (bytes per row of the first plane) of an image of a width.
+	pub fn min_pitch(&self, width: u32) -> u32 {
+		match *self {
+			ResourceFormat::Rgb565 => width * 2,
+			ResourceFormat::Rgba32 => width * 4,
+			ResourceFormat::Yuv420 => width,
+		}
+	}
+	/// The number of bytes of an image of a height and pitch.
+	pub fn data_size(&self, height: u32, pitch: u32) -> usize {
+		match *self {
+			ResourceFormat::Rgb565 | ResourceFormat::Rgba32 => (height * pitch) as usize,
+			ResourceFormat::Yuv420 => {
+				let height = (height + 15) & !15;
+				(height * pitch + 2 * (height / 2) * (pitch / 2)) as usize
+			},
+		}
+	}
+}
+
+/// A dispmanx element showing an image without GL, e.g. a splash screen or a static background.
+///
+/// Only supported on the dispmanx platform.
+/// Elements are stacked by their layer: a layer below `WindowConfig::layer` of a window is shown beneath the window,
+/// a layer above it on top of the window. Use an alpha channel in the window or the image to see through.
+pub struct DispmanxImageLayer<S> where S: Deref<Target=System> {
+	/// The system.
+	pub system: S,
+	/// The display the image is shown on.
+	display: Display,
+	/// Dispmanx display.
+	dispmanx_display: ffi::DispmanxDisplayHandle,
+	/// Dispmanx resource holding the pixels.
+	resource: ffi::DispmanxResourceHandle,
+	/// Dispmanx element. DISPMANX_NO_HANDLE while hidden.
+	element: ffi::DispmanxElementHandle,
+	/// The pixel format of the resource.
+	format: ResourceFormat,
+	/// The size of the resource.
+	dimensions: (u32, u32),
+}
+impl<S> DispmanxImageLayer<S> where S: Deref<Target=System> {
+	/// Create a hidden image of a pixel format and size. The pixels are undefined until written.
+	pub fn new(system: S, display: Display, format: ResourceFormat, dimensions: (u32, u32)) -> Result<Self, Error> {
+		let mut layer = DispmanxImageLayer {
+			system: system,
+			display: display,
+			dispmanx_display: ffi::DISPMANX_NO_HANDLE,
+			resource: ffi::DISPMANX_NO_HANDLE,
+			element: ffi::DISPMANX_NO_HANDLE,
+			format: format,
+			dimensions: dimensions,
+		};
+		{
+			let lib_bcm_host = try!(layer.system.lib_bcm_host());
+			let _guard = layer.system.mutex.lock().unwrap();
+			unsafe {
+				let (width, height) = dimensions;
+				let mut native_image: libc::uint32_t = 0;
+				layer.resource = (lib_bcm_host.vc_dispmanx_resource_create)(format.image_type(), width, height, &mut native_image as *mut libc::uint32_t);
+				if layer.resource == ffi::DISPMANX_NO_HANDLE { return Err(Error::Fn("vc_dispmanx_resource_create")); }
+				layer.dispmanx_display = (lib_bcm_host.vc_dispmanx_display_open)(display.index() as libc::uint32_t);
+				if layer.dispmanx_display == ffi::DISPMANX_NO_HANDLE { return Err(Error::Fn("vc_dispmanx_display_open")); }
+			}
+		}
+		Ok(layer)
+	}
+	/// The display the image is shown on.
+	pub fn display(&self) -> Display {
+		self.display
+	}
+	/// The pixel format of the image.
+	pub fn format(&self) -> ResourceFormat {
+		self.format
+	}
+	/// The size of the image.
+	pub fn dimensions(&self) -> (u32, u32) {
+		self.dimensions
+	}
+	/// Whether the image is shown.
+	pub fn is_shown(&self) -> bool {
+		self.element != ffi::DISPMANX_NO_HANDLE
+	}
+	/// Upload the pixels of the whole image, row by row from the top. The pitch is the number of bytes per row (of the Y plane for YUV420).
+	pub fn write(&mut self, pixels: &[u8], pitch: u32) -> Result<(), Error> {
+		let (width, height) = self.dimensions;
+		if pitch < self.format.min_pitch(width) { return Err(Error::Unsupported("the pitch is smaller than a row of the image")); }
+		if pixels.len() < self.format.data_size(height, pitch) { return Err(Error::Unsupported("too few pixels for the size of the image")); }
+		let lib_bcm_host = try!(self.system.lib_bcm_host());
+		let _guard = self.system.mutex.lock().unwrap();
+		let rect = ffi::VcRect { x: 0, y: 0, width: width as libc::int32_t, height: height as libc::int32_t };
+		if unsafe {
+			(lib_bcm_host.vc_dispmanx_resource_write_data)(
+				self.resource,
+				self.format.image_type(),
+				pitch as libc::c_int,
+				pixels.as_ptr() as *const libc::c_void,
+				&rect as *const ffi::VcRect
+			)
+		} != ffi::DISPMANX_SUCCESS { return Err(Error::Fn("vc_dispmanx_resource_write_data")); }
+		Ok(())
+	}
+	/// Show the image scaled to the whole display on a layer. Replaces the element if the image is already shown.
+	pub fn show(&mut self, layer: i32) -> Result<(), Error> {
+		let display_size = try!(self.system.display_size(self.display));
+		self.show_at(layer, Rect::from_size(display_size))
+	}
+	/// Show the image scaled to a rectangle of the display on a layer. Replaces the element if the image is already shown.
+	pub fn show_at(&mut self, layer: i32, destination: Rect) -> Result<(), Error> {
+		let DispmanxImageLayer { ref system, dispmanx_display, resource, ref mut element, dimensions, .. } = *self;
+		system.with_update(|lib_bcm_host, update| {
+			unsafe { add(lib_bcm_host, update, element, dispmanx_display, resource, dimensions, layer, destination) }
+		})
+	}
+	/// Remove the element. The pixels are kept, so the image can be shown again. Does nothing if the image is hidden.
+	pub fn hide(&mut self) -> Result<(), Error> {
+		if self.element == ffi::DISPMANX_NO_HANDLE { return Ok(()); }
+		let DispmanxImageLayer { ref system, ref mut element, .. } = *self;
+		system.with_update(|lib_bcm_host, update| { unsafe { remove(lib_bcm_host, update, element) } })
+	}
+}
+
+impl<S> Drop for DispmanxImageLayer<S> where S: Deref<Target=System> {
+	fn drop(&mut self) {
+		let _ = self.hide();
+		let lib_bcm_host = match self.system.lib_bcm_host() {
+			Ok(lib_bcm_host) => lib_bcm_host,
+			Err(_) => return,
+		};
+		let _guard = self.system.mutex.lock().unwrap();
+		unsafe {
+			if self.dispmanx_display != ffi::DISPMANX_NO_HANDLE {
+				assert!((lib_bcm_host.vc_dispmanx_display_close)(self.dispmanx_display) == ffi::DISPMANX_SUCCESS);
+				self.dispmanx_display = ffi::DISPMANX_NO_HANDLE;
+			}
+			if self.resource != ffi::DISPMANX_NO_HANDLE {
+				assert!((lib_bcm_host.vc_dispmanx_resource_delete)(self.resource) == ffi::DISPMANX_SUCCESS);
+				self.resource = ffi::DISPMANX_NO_HANDLE;
+			}
+		}
+	}
+}
+
+/// The dispmanx element of an image layer. DISPMANX_NO_HANDLE while hidden.
+pub fn element<S>(image_layer: &DispmanxImageLayer<S>) -> ffi::DispmanxElementHandle where S: Deref<Target=System> {
+	image_layer.element
+}
+
+/// Add the element of an image layer in an update, replacing a shown element. The mutex of the system must be locked.
+pub unsafe fn add_element<S>(image_layer: &mut DispmanxImageLayer<S>, lib_bcm_host: &ffi::LibBcmHost, update: ffi::DispmanxUpdateHandle, layer: i32, destination: Rect) -> Result<(), Error> where S: Deref<Target=System> {
+	let DispmanxImageLayer { dispmanx_display, resource, ref mut element, dimensions, .. } = *image_layer;
+	add(lib_bcm_host, update, element, dispmanx_display, resource, dimensions, layer, destination)
+}
+
+/// Remove the element of an image layer in an update. Does nothing if the image is hidden. The mutex of the system must be locked.
+pub unsafe fn remove_element<S>(image_layer: &mut DispmanxImageLayer<S>, lib_bcm_host: &ffi::LibBcmHost, update: ffi::DispmanxUpdateHandle) -> Result<(), Error> where S: Deref<Target=System> {
+	remove(lib_bcm_host, update, &mut image_layer.element)
+}
+
+// Add an element showing a resource of a size scaled to a rectangle of the display, replacing the element given. The mutex of the system must be locked.
+unsafe fn add(lib_bcm_host: &ffi::LibBcmHost, update: ffi::DispmanxUpdateHandle, element: &mut ffi::DispmanxElementHandle, display: ffi::DispmanxDisplayHandle, resource: ffi::DispmanxResourceHandle, dimensions: (u32, u32), layer: i32, destination: Rect) -> Result<(), Error> {
+	try!(remove(lib_bcm_host, update, element));
+	*element = try!(dispmanx::add_element(lib_bcm_host, update, display, layer, resource, FixedRect::from(Rect::from_size(dimensions)), destination));
+	Ok(())
+}
+
+// Remove an element in an update. Does nothing for DISPMANX_NO_HANDLE. The mutex of the system must be locked.
+unsafe fn remove(lib_bcm_host: &ffi::LibBcmHost, update: ffi::DispmanxUpdateHandle, element: &mut ffi::DispmanxElementHandle) -> Result<(), Error> {
+	if *element == ffi::DISPMANX_NO_HANDLE { return Ok(()); }
+	if (lib_bcm_host.vc_dispmanx_element_remove)(update, *element) != ffi::DISPMANX_SUCCESS { return Err(Error::Fn("vc_dispmanx_element_remove")); }
+	*element = ffi::DISPMANX_NO_HANDLE;
+	Ok(())
+}
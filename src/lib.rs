@@ -5,6 +5,32 @@ Note:
 This library does not provide any glutin functionality.
 So there is no event mechanism to get mouse or keyboard input from.
 
+On images using the full KMS driver (vc4-kms, e.g. Raspberry Pi 4/5) dispmanx is not available.
+Use `System::new_kms` there, which renders to GBM surfaces and page flips with KMS/DRM.
+`System::autodetect` tries the available backends in turn and reports why the ones before the chosen one failed.
+`PiDisplay` owns a window and its facade and provides `draw` like the glutin display of glium.
+`PiFacade` creates either the bare context or a `PiDisplay`, as chosen by a `FacadeType`.
+The `glium_pib-info` tool prints the libraries, the displays, the EGL configs and the driver strings of a system.
+`DispmanxImageLayer` shows static images, e.g. a splash screen, beneath or above the windows without using GL.
+`System::start_update` batches changes of windows and image layers as well as added and removed windows into one dispmanx update, so they are shown together without tearing.
+`Update::submit_async` returns without waiting for the vsync, `PendingUpdate` tells when the update is shown.
+`Window::safe_area` is the part of the framebuffer visible inside the overscan of TVs, `WindowConfig::inset_overscan` scales the whole surface into it where the firmware does not compensate the overscan (disable_overscan=1).
+
+# Threading
+A `System` is `Send` and `Sync` and is usually shared as `Arc<System>`.
+Calls into dispmanx, the TV service and DRM are serialized by the system, EGL itself is thread safe.
+Waiting for the page flip of a KMS window does not hold the lock of the system, so windows on other threads are not blocked meanwhile.
+A `Window` is bound to the thread its context is current on.
+To render on another thread, convert it with `Window::into_handle`, send the `WindowHandle` and call `WindowHandle::into_window` there.
+The glium facade (`create_facade`) has to be created on the rendering thread.
+Dropping a window or converting it into a handle releases the EGL state of the thread (`eglReleaseThread`).
+
+# Features
+- `log`: `log_debug_callback` routes the debug messages of the driver into the `log` crate.
+- `serialize`: serde support for the configuration types.
+- `config-file`: `Settings` loads the library directory and the window configuration from a TOML file and environment variables.
+- `camera`: `Camera` reads the frames of the raspberry pi camera through MMAL. Without a camera `SyntheticSource` provides frames through the same `FrameSource` trait. `VideoFrame::texture` shows a frame of either by a texture.
+
 # Example
 ```no_run
 #[macro_use] extern crate glium;
@@ -18,7 +44,6 @@ implement_vertex!(Vertex, position);
 
 fn main() {
 	use std::default::Default;
-	use std::sync::Arc;
 	use std::rc::Rc;
 	
 	use glium::DisplayBuild;
@@ -34,24 +59,15 @@ fn main() {
 		Ok(f) => f.get_context().clone(),
 		Err(_) => {
 			println!("Failed to create X window.");
-			println!("Trying to use broadcom libraries for the raspberry pi.");
-			let system = glium_pib::System::new(Default::default());
-			let system = match system {
-				Ok(s) => s,
-				Err(_) => {
-					println!("Failed to use broadcom libraries.");
-					return;
-				}
-			};
-			let system = Arc::new(system);
-			let facade = glium_pib::create_window_facade(
-				&system,
-				&std::default::Default::default()
-			);
-			match facade {
-				Ok(f) => f,
+			println!("Trying to use the raspberry pi.");
+			let display = glium_pib::PiWindowBuilder::new()
+				.with_dimensions(1024, 768)
+				.with_title(format!("Hello world"))
+				.build_glium();
+			match display {
+				Ok(d) => d.get_context().clone(),
 				Err(_) => {
-					println!("Failed to use broadcom libraries.");
+					println!("Failed to use the raspberry pi.");
 					return;
 				},
 			}
@@ -110,34 +126,87 @@ fn main() {
 #[macro_use] extern crate shared_library;
 #[macro_use] extern crate glium;
 extern crate libc;
+#[cfg(feature = "log")]
+#[macro_use] extern crate log;
+#[cfg(feature = "serialize")]
+extern crate serde;
+#[cfg(feature = "serialize")]
+#[macro_use] extern crate serde_derive;
+#[cfg(feature = "config-file")]
+extern crate toml;
 
 mod ffi;
 mod error;
 mod config;
+mod edid;
+mod power;
+mod dispmanx;
+mod kms;
+mod detect;
+mod facade;
+mod builder;
+mod info;
+mod image;
+mod layer;
+mod update;
+mod frame;
+#[cfg(feature = "camera")]
+mod camera;
+#[cfg(feature = "config-file")]
+mod settings;
+#[cfg(feature = "log")]
+mod debug;
 
 use std::sync::atomic::{Ordering, AtomicBool, ATOMIC_BOOL_INIT};
-use std::sync::Mutex;
-use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::ops::Deref;
-use std::default::Default;
 use std::path::Path;
+use std::ffi::{CStr, CString};
 
 pub use error::Error;
 use error::gl_error;
-pub use config::{LibDir, Display, ColorBits, DepthBits, WindowConfig};
+pub use config::{LibDir, LibraryNames, KmsConfig, Display, ColorBits, DepthBits, GlesVersion, GlesVersionRange, Robustness, Rect, FixedRect, Overscan, Scaling, SurfaceMapping, WindowConfig};
+pub use edid::{Edid, DetailedTiming, EDID_BLOCK_SIZE};
+pub use detect::{Probe, DetectionReport};
+pub use builder::PiWindowBuilder;
+pub use info::{EglConfigInfo, EglInfo, GlInfo};
+pub use image::{ImageSource, EglImage, ImageTexture, ExternalTexture};
+pub use layer::{DispmanxImageLayer, ResourceFormat};
+pub use update::{Update, PendingUpdate, ElementChange};
+pub use frame::{FrameSource, VideoFrame, FrameTexture, SyntheticSource, SyntheticFrame};
+#[cfg(feature = "camera")]
+pub use camera::{Camera, CameraConfig, CameraFrame};
+#[cfg(feature = "config-file")]
+pub use settings::Settings;
+pub use facade::{FacadeOptions, FacadeType, PiFacade, PiDisplay, create_facade, create_facade_with_debug, create_facade_with_options, create_window_facade, create_window_facade_with_options};
+#[cfg(feature = "log")]
+pub use debug::log_debug_callback;
 
 use shared_library::dynamic_library::DynamicLibrary;
+use glium::backend::Backend;
 
 
-// System singleton guard (The system may be created just once during the whole lifetime of the process).
+// System singleton guard (Only one system may exist at a time).
 static SYSTEM_SINGLETON_GUARD: AtomicBool = ATOMIC_BOOL_INIT;
-/// Process wide shared data. Only one instance may be created per process.
+
+/// The platform used to show the EGL surfaces.
+enum Platform {
+	/// Broadcom dispmanx using the Bcm-Host library. Shared with the elements which remove themselves when dropped.
+	Dispmanx(Arc<ffi::LibBcmHost>),
+	/// KMS/DRM with GBM surfaces.
+	Kms(kms::Device),
+	/// Mesa without any display. Windows render to pbuffers.
+	Surfaceless,
+}
+
+/// Process wide shared data. Only one instance may exist at a time, another one may be created after it has been dropped.
 pub struct System {
 	/// The library directory.
 	lib_dir: LibDir,
-	// Bcm-Host library.
-	lib_bcm_host: ffi::LibBcmHost,
+	/// The names of the libraries loaded.
+	library_names: LibraryNames,
+	// The platform.
+	platform: Platform,
 	// GLES library.
 	lib_glesv2: ffi::LibGLESv2,
 	// Dynamic library used to enable glium to load any symbol.
@@ -146,101 +215,470 @@ pub struct System {
 	lib_egl: ffi::LibEGL,
 	/// Egl display.
 	egl_display: ffi::EGLDisplay,
+	/// EGL version (major, minor) returned by eglInitialize.
+	egl_version: (u32, u32),
+	/// Extensions of the EGL display.
+	egl_extensions: Vec<String>,
+	/// Functions of the image extensions.
+	image_functions: image::ImageFunctions,
 	/// Mutex used to protect potential unsynchronized functionality of the ffi.
 	mutex: Mutex<()>,
+	/// Elements used to blank displays.
+	blank_layers: Mutex<Vec<power::BlankLayer>>,
+	/// The state of the analog output when the outputs were powered off. None if it was off.
+	analog_state: Mutex<Option<ffi::SdtvDisplayState>>,
 }
 impl System {
-	/// Create a new system using the libraries from the library directory specified. This should only be called once per process.
+	/// Create a new system using the libraries from the library directory specified. Fails with `Error::AlreadyInitialized` if another system exists.
+	/// Tries the names of `LibraryNames::search_list`.
 	pub fn new(lib_dir: LibDir) -> Result<Self, Error> {
+		System::with_library_names(lib_dir, &LibraryNames::search_list())
+	}
+	/// Create a new system using the first of the library names which can be loaded from the library directory.
+	/// Fails with `Error::AlreadyInitialized` if another system exists.
+	pub fn with_library_names(lib_dir: LibDir, names: &[LibraryNames]) -> Result<Self, Error> {
+		System::singleton(|| { System::load_library_names(lib_dir, names) })
+	}
+	/// Create a new system using the first of the library names which can be loaded. The singleton guard must be set.
+	fn load_library_names(lib_dir: LibDir, names: &[LibraryNames]) -> Result<Self, Error> {
+		let mut first_error = None;
+		for library_names in names {
+			let lib_bcm_host = ffi::LibBcmHost::open(&lib_dir.join(&library_names.bcm_host)).map_err(|e| { Error::Sl(e) });
+			let result = lib_bcm_host.and_then(|lib_bcm_host| {
+				System::with_platform(lib_dir.clone(), library_names.clone(), Platform::Dispmanx(Arc::new(lib_bcm_host)))
+			});
+			match result {
+				// Only try the next names if loading a library failed.
+				Err(Error::Sl(e)) => if first_error.is_none() { first_error = Some(Error::Sl(e)); },
+				Err(Error::Dl(e)) => if first_error.is_none() { first_error = Some(Error::Dl(e)); },
+				result => return result,
+			}
+		}
+		Err(first_error.unwrap_or(Error::Unsupported("no library names given")))
+	}
+	/// Create a new system using KMS/DRM and GBM instead of dispmanx (e.g. for the vc4-kms driver). Fails with `Error::AlreadyInitialized` if another system exists.
+	pub fn new_kms(mut config: KmsConfig) -> Result<Self, Error> {
+		System::singleton(move || {
+			let file = config.file.take();
+			let device = try!(kms::Device::open(&config, file));
+			System::with_platform(config.lib_dir, LibraryNames::mesa(), Platform::Kms(device))
+		})
+	}
+	/// Create a new system using Mesa without any display (EGL_MESA_platform_surfaceless).
+	/// Windows render offscreen and need an explicit surface size. Fails with `Error::AlreadyInitialized` if another system exists.
+	pub fn new_surfaceless(lib_dir: LibDir) -> Result<Self, Error> {
+		System::singleton(|| { System::with_platform(lib_dir, LibraryNames::mesa(), Platform::Surfaceless) })
+	}
+	/// Set the singleton guard before creating a system, so that no device is opened while another system exists.
+	/// Another system may be created if the creation fails.
+	fn singleton<F>(create: F) -> Result<Self, Error> where F: FnOnce() -> Result<Self, Error> {
+		if SYSTEM_SINGLETON_GUARD.swap(true, Ordering::AcqRel) { return Err(Error::AlreadyInitialized); }
+		let result = create();
+		if result.is_err() { SYSTEM_SINGLETON_GUARD.store(false, Ordering::Release); }
+		result
+	}
+	/// Create a new system on a platform. The singleton guard must be set.
+	fn with_platform(lib_dir: LibDir, library_names: LibraryNames, platform: Platform) -> Result<Self, Error> {
 
 		// Create the mutex.
 		let mutex: Mutex<()> = Mutex::new(());
 		
 		// Load the libraries needed.
-		let lib_bcm_host = try!(
-			ffi::LibBcmHost::open(&lib_dir.join("libbcm_host.so")).map_err(|e| { Error::Sl(e) })
-		);
 		let lib_glesv2 = try!(
-			ffi::LibGLESv2::open(&lib_dir.join("libGLESv2.so")).map_err(|e| { Error::Sl(e) })
+			ffi::LibGLESv2::open(&lib_dir.join(&library_names.glesv2)).map_err(|e| { Error::Sl(e) })
 		);
 		let dlib_glesv2 = try!(
-			DynamicLibrary::open(Some(&lib_dir.join("libGLESv2.so"))).map_err(|e| { Error::Dl(e) })
+			DynamicLibrary::open(Some(&lib_dir.join(&library_names.glesv2))).map_err(|e| { Error::Dl(e) })
 		);
 		let lib_egl = try!(
-			ffi::LibEGL::open(&lib_dir.join("libEGL.so")).map_err(|e| { Error::Sl(e) })
+			ffi::LibEGL::open(&lib_dir.join(&library_names.egl)).map_err(|e| { Error::Sl(e) })
 		);
 
-		assert!(SYSTEM_SINGLETON_GUARD.swap(true, Ordering::AcqRel) == false);
+		let (egl_display, egl_version) = try!(unsafe { System::initialize(&platform, &lib_egl, &lib_glesv2, &mutex) });
 
-		let egl_display = unsafe {
-		
-			// Lock the mutex.
-			let _ = mutex.lock();
-
-			(lib_bcm_host.bcm_host_init)();
-
-			// Get the default egl display.
-			let egl_display = (lib_egl.eglGetDisplay)(ffi::EGL_DEFAULT_DISPLAY);
-			if egl_display == ffi::EGL_NO_DISPLAY { return Err(Error::Fn("eglGetDisplay")); }
-			try!{gl_error(&lib_glesv2, "eglGetDisplay")};
-
-			// Initialize EGL.
-			if (lib_egl.eglInitialize)(egl_display, 0 as *mut ffi::EGLint, 0 as *mut ffi::EGLint) == 0 { return Err(Error::Fn("eglInitialize")); }
-			try!{gl_error(&lib_glesv2, "eglInitialize")};
-
-			egl_display
-		};
+		let egl_extensions = unsafe { egl_extensions(&lib_egl, egl_display) };
+		let image_functions = unsafe { image::ImageFunctions::load(&lib_egl) };
 
 		// Create and return system.
 		Ok(System {
 			lib_dir: lib_dir,
-			lib_bcm_host: lib_bcm_host,
+			library_names: library_names,
+			platform: platform,
 			lib_glesv2: lib_glesv2,
 			dlib_glesv2: dlib_glesv2,
 			lib_egl: lib_egl,
 			egl_display: egl_display,
+			egl_version: egl_version,
+			egl_extensions: egl_extensions,
+			image_functions: image_functions,
 			mutex: mutex,
+			blank_layers: Mutex::new(Vec::new()),
+			analog_state: Mutex::new(None),
 		})
 	}
+	/// Initialize the platform and EGL. Returns the egl display and the EGL version.
+	unsafe fn initialize(platform: &Platform, lib_egl: &ffi::LibEGL, lib_glesv2: &ffi::LibGLESv2, mutex: &Mutex<()>) -> Result<(ffi::EGLDisplay, (u32, u32)), Error> {
+
+		// Lock the mutex.
+		let _guard = mutex.lock().unwrap();
+
+		// Get the egl display of the platform.
+		let egl_display = match *platform {
+			Platform::Dispmanx(ref lib_bcm_host) => {
+				(lib_bcm_host.bcm_host_init)();
+				(lib_egl.eglGetDisplay)(ffi::EGL_DEFAULT_DISPLAY)
+			},
+			Platform::Kms(ref device) => (lib_egl.eglGetDisplay)(device.native_display()),
+			Platform::Surfaceless => {
+				// Client extensions are queried without a display.
+				let client_extensions = egl_extensions(lib_egl, ffi::EGL_NO_DISPLAY);
+				if !client_extensions.iter().any(|e| { e == "EGL_EXT_platform_base" }) { return Err(Error::Unsupported("EGL_EXT_platform_base")); }
+				if !client_extensions.iter().any(|e| { e == "EGL_MESA_platform_surfaceless" }) { return Err(Error::Unsupported("EGL_MESA_platform_surfaceless")); }
+				let name = b"eglGetPlatformDisplayEXT\0";
+				let get_platform_display = (lib_egl.eglGetProcAddress)(name.as_ptr() as *const libc::c_char);
+				if get_platform_display.is_null() { return Err(Error::Fn("eglGetProcAddress")); }
+				let get_platform_display: ffi::EglGetPlatformDisplayExt = std::mem::transmute(get_platform_display);
+				get_platform_display(ffi::EGL_PLATFORM_SURFACELESS_MESA, ffi::EGL_DEFAULT_DISPLAY, 0 as *const ffi::EGLint)
+			},
+		};
+		let result = System::initialize_egl(egl_display, lib_egl, lib_glesv2);
+		if result.is_err() {
+			match *platform {
+				Platform::Dispmanx(ref lib_bcm_host) => (lib_bcm_host.bcm_host_deinit)(),
+				_ => {},
+			}
+		}
+		result
+	}
+	/// Initialize the egl display. Returns the display and the EGL version.
+	unsafe fn initialize_egl(egl_display: ffi::EGLDisplay, lib_egl: &ffi::LibEGL, lib_glesv2: &ffi::LibGLESv2) -> Result<(ffi::EGLDisplay, (u32, u32)), Error> {
+		if egl_display == ffi::EGL_NO_DISPLAY { return Err(Error::Fn("eglGetDisplay")); }
+		try!{gl_error(lib_glesv2, "eglGetDisplay")};
+
+		// Initialize EGL.
+		let mut major: ffi::EGLint = 0;
+		let mut minor: ffi::EGLint = 0;
+		if (lib_egl.eglInitialize)(egl_display, &mut major as *mut ffi::EGLint, &mut minor as *mut ffi::EGLint) == 0 { return Err(Error::Fn("eglInitialize")); }
+		try!{gl_error(lib_glesv2, "eglInitialize")};
+
+		Ok((egl_display, (major as u32, minor as u32)))
+	}
+	/// The EGL version (major, minor) returned by eglInitialize.
+	pub fn egl_version(&self) -> (u32, u32) {
+		self.egl_version
+	}
+	/// Whether a feature is available: either it is part of the EGL version given or the extension is supported.
+	fn has_egl_feature(&self, core_version: (u32, u32), extension: &str) -> bool {
+		self.egl_version >= core_version || self.has_egl_extension(extension)
+	}
+	/// The Bcm-Host library. Fails if the system does not use dispmanx.
+	fn lib_bcm_host(&self) -> Result<&Arc<ffi::LibBcmHost>, Error> {
+		match self.platform {
+			Platform::Dispmanx(ref lib_bcm_host) => Ok(lib_bcm_host),
+			_ => Err(Error::Unsupported("requires the dispmanx platform")),
+		}
+	}
+	/// Run a function with a new dispmanx update while the mutex is locked. The update is submitted synchronously afterwards.
+	fn with_update<F>(&self, f: F) -> Result<(), Error> where F: FnOnce(&ffi::LibBcmHost, ffi::DispmanxUpdateHandle) -> Result<(), Error> {
+		let lib_bcm_host = try!(self.lib_bcm_host());
+		let _guard = self.mutex.lock().unwrap();
+		unsafe {
+			let update = (lib_bcm_host.vc_dispmanx_update_start)(0);
+			if update == ffi::DISPMANX_NO_HANDLE { return Err(Error::Fn("vc_dispmanx_update_start")); }
+			let result = f(lib_bcm_host, update);
+			if (lib_bcm_host.vc_dispmanx_update_submit_sync)(update) != ffi::DISPMANX_SUCCESS && result.is_ok() { return Err(Error::Fn("vc_dispmanx_update_submit_sync")); }
+			result
+		}
+	}
 	/// Get the size of a display.
 	unsafe fn display_size_no_lock(&self, display: Display) -> Result<(u32, u32), Error> {
-		// Get display size.
-		let mut width: libc::uint32_t = 0;
-		let mut height: libc::uint32_t = 0;
-		let res = (self.lib_bcm_host.graphics_get_display_size)(
-			display.index(),
-			&mut width as *mut libc::uint32_t,
-			&mut height as *mut libc::uint32_t
-		);
-		if res < 0 { return Err(Error::Fn("graphics_get_display_size")); }
-		Ok((width as u32, height as u32))
+		match self.platform {
+			Platform::Dispmanx(ref lib_bcm_host) => {
+				// Get display size.
+				let mut width: libc::uint32_t = 0;
+				let mut height: libc::uint32_t = 0;
+				let res = (lib_bcm_host.graphics_get_display_size)(
+					display.index(),
+					&mut width as *mut libc::uint32_t,
+					&mut height as *mut libc::uint32_t
+				);
+				if res < 0 { return Err(Error::Fn("graphics_get_display_size")); }
+				Ok((width as u32, height as u32))
+			},
+			Platform::Kms(ref device) => device.output(display, None).map(|output| { output.size() }),
+			Platform::Surfaceless => Err(Error::Unsupported("the surfaceless platform has no displays")),
+		}
 	}
 	/// Get the size of a display.
 	pub fn display_size(&self, display: Display) -> Result<(u32, u32), Error> {
-		let _ = self.mutex.lock();
+		let _guard = self.mutex.lock().unwrap();
 		unsafe { self.display_size_no_lock(display) }
 	}
+	/// Read and parse the EDID of a display. Only supported for Hdmi on dispmanx.
+	pub fn edid(&self, display: Display) -> Result<Edid, Error> {
+		let _guard = self.mutex.lock().unwrap();
+		let lib_bcm_host = match self.platform {
+			Platform::Dispmanx(ref lib_bcm_host) => lib_bcm_host,
+			Platform::Kms(ref device) => return Edid::parse(&try!(unsafe { device.edid(display) })),
+			Platform::Surfaceless => return Err(Error::Unsupported("the surfaceless platform has no displays")),
+		};
+		match display {
+			Display::Hdmi => {},
+			_ => return Err(Error::Unsupported("EDID is only available for Hdmi")),
+		}
+		let mut data: Vec<u8> = Vec::with_capacity(EDID_BLOCK_SIZE);
+		let mut block_count = 1;
+		let mut index = 0;
+		while index < block_count {
+			let mut block = [0u8; EDID_BLOCK_SIZE];
+			let read = unsafe {
+				(lib_bcm_host.vc_tv_hdmi_ddc_read)(
+					(index * EDID_BLOCK_SIZE) as libc::uint32_t,
+					EDID_BLOCK_SIZE as libc::uint32_t,
+					block.as_mut_ptr()
+				)
+			};
+			if read != EDID_BLOCK_SIZE as libc::c_int { return Err(Error::Fn("vc_tv_hdmi_ddc_read")); }
+			// The base block announces the number of extension blocks.
+			if index == 0 { block_count += block[126] as usize; }
+			data.extend_from_slice(&block);
+			index += 1;
+		}
+		Edid::parse(&data)
+	}
+	/// The overscan which the firmware does not compensate: overscan_left, ... in config.txt if disable_overscan=1.
+	/// Zero otherwise, as the firmware shrinks the picture by the overscan itself then. Only supported on dispmanx.
+	pub fn firmware_overscan(&self) -> Result<Overscan, Error> {
+		let _guard = self.mutex.lock().unwrap();
+		unsafe { self.firmware_overscan_no_lock() }
+	}
+	/// Get the overscan which the firmware does not compensate.
+	unsafe fn firmware_overscan_no_lock(&self) -> Result<Overscan, Error> {
+		if try!(self.config_value("disable_overscan")) == 0 { return Ok(Overscan::default()); }
+		Ok(Overscan {
+			left: try!(self.config_value("overscan_left")),
+			right: try!(self.config_value("overscan_right")),
+			top: try!(self.config_value("overscan_top")),
+			bottom: try!(self.config_value("overscan_bottom")),
+		})
+	}
+	/// Read a numeric value of the firmware configuration. Values which are not set are 0.
+	unsafe fn config_value(&self, name: &str) -> Result<u32, Error> {
+		let lib_bcm_host = try!(self.lib_bcm_host());
+		let command = CString::new(format!("get_config {}", name)).unwrap();
+		let mut response = [0 as libc::c_char; 256];
+		if (lib_bcm_host.vc_gencmd)(response.as_mut_ptr(), response.len() as libc::c_int, b"%s\0".as_ptr() as *const libc::c_char, command.as_ptr()) != 0 { return Err(Error::Fn("vc_gencmd")); }
+		// The response is "name=value", or "error=..." for unknown names.
+		let response = CStr::from_ptr(response.as_ptr()).to_string_lossy().into_owned();
+		let mut parts = response.trim().splitn(2, '=');
+		match (parts.next(), parts.next()) {
+			(Some(key), Some(value)) if key == name => Ok(value.trim().parse().unwrap_or(0)),
+			_ => Ok(0),
+		}
+	}
 	/// The library directory in use.
 	pub fn lib_dir(&self) -> &Path {
 		self.lib_dir.deref()
 	}
+	/// The names of the libraries loaded from the library directory.
+	pub fn library_names(&self) -> &LibraryNames {
+		&self.library_names
+	}
+	/// Release the EGL state of the calling thread. Call this before a thread exits which used a context
+	/// without dropping a `Window` or calling `Window::into_handle` on it afterwards.
+	pub fn release_thread(&self) {
+		unsafe { (self.lib_egl.eglReleaseThread)(); }
+	}
+	/// The DRM device in use. None if the system does not use KMS/DRM.
+	pub fn drm_device(&self) -> Option<&Path> {
+		match self.platform {
+			Platform::Kms(ref device) => Some(device.path()),
+			_ => None,
+		}
+	}
 }
 impl Drop for System {
 	fn drop(&mut self) {
 		unsafe {
+			// Remove blanking elements before the Bcm-Host library is deinitialized.
+			{
+				let _guard = self.mutex.lock().unwrap();
+				self.blank_layers.lock().unwrap().clear();
+			}
 			// Finelize EGL.
 			if self.egl_display != ffi::EGL_NO_DISPLAY {
 				assert!((self.lib_egl.eglTerminate)(self.egl_display) != 0);
 				self.egl_display = ffi::EGL_NO_DISPLAY;
 			}
 			// Finalize 
-			(self.lib_bcm_host.bcm_host_deinit)();
+			match self.platform {
+				Platform::Dispmanx(ref lib_bcm_host) => (lib_bcm_host.bcm_host_deinit)(),
+				Platform::Kms(_) | Platform::Surfaceless => {},
+			}
 		}
-		//SYSTEM_SINGLETON_GUARD.store(false, Ordering::Release);
+		// Release the platform (e.g. the DRM device) before another system may be created.
+		drop(std::mem::replace(&mut self.platform, Platform::Surfaceless));
+		SYSTEM_SINGLETON_GUARD.store(false, Ordering::Release);
 	}
 }
 unsafe impl Sync for System {}
+unsafe impl Send for System {}
+
+
+/// Choose an EGL config for a window configuration, surface type and OpenGL ES version. The mutex of the system must be locked.
+unsafe fn choose_config(system: &System, config: &WindowConfig, surface_type: ffi::GLenum, version: GlesVersion) -> Result<ffi::EGLConfig, Error> {
+	let mut attribute_list: Vec<ffi::EGLint> = vec![
+		ffi::EGL_SURFACE_TYPE as ffi::EGLint, surface_type as ffi::EGLint,
+		ffi::EGL_RED_SIZE as ffi::EGLint, config.red.0 as ffi::EGLint,
+		ffi::EGL_GREEN_SIZE as ffi::EGLint, config.green.0 as ffi::EGLint,
+		ffi::EGL_BLUE_SIZE as ffi::EGLint, config.blue.0 as ffi::EGLint,
+	];
+	match config.alpha.as_ref() {
+		Some(alpha) => attribute_list.extend_from_slice(&[ffi::EGL_ALPHA_SIZE as ffi::EGLint, alpha.0 as ffi::EGLint]),
+		None => {},
+	}
+	match config.depth.as_ref() {
+		Some(depth) => attribute_list.extend_from_slice(&[ffi::EGL_DEPTH_SIZE as ffi::EGLint, depth.0 as ffi::EGLint]),
+		None => {},
+	}
+	if config.samples > 0 {
+		attribute_list.extend_from_slice(&[
+			ffi::EGL_SAMPLE_BUFFERS as ffi::EGLint, 1,
+			ffi::EGL_SAMPLES as ffi::EGLint, config.samples as ffi::EGLint,
+		]);
+	}
+	if version.0 >= 3 {
+		if !system.has_egl_feature((1, 5), "EGL_KHR_create_context") { return Err(Error::Unsupported("EGL_KHR_create_context")); }
+		attribute_list.extend_from_slice(&[ffi::EGL_RENDERABLE_TYPE as ffi::EGLint, ffi::EGL_OPENGL_ES3_BIT_KHR as ffi::EGLint]);
+	}
+	attribute_list.push(ffi::EGL_NONE as ffi::EGLint);
+
+	// Get all matching configs, sorted by EGL.
+	let mut egl_num_config: ffi::EGLint = 0;
+	if (system.lib_egl.eglChooseConfig)(system.egl_display, attribute_list.as_ptr(), 0 as *mut ffi::EGLConfig, 0, &mut egl_num_config as *mut ffi::EGLint) == 0 { return Err(Error::Fn("eglChooseConfig")); }
+	try!{gl_error(&system.lib_glesv2, "eglChooseConfig")};
+	if egl_num_config == 0 { return Err(Error::Fn("eglChooseConfig")); }
+	let mut egl_configs: Vec<ffi::EGLConfig> = vec![0 as ffi::EGLConfig; egl_num_config as usize];
+	if (system.lib_egl.eglChooseConfig)(system.egl_display, attribute_list.as_ptr(), egl_configs.as_mut_ptr(), egl_num_config, &mut egl_num_config as *mut ffi::EGLint) == 0 { return Err(Error::Fn("eglChooseConfig")); }
+	egl_configs.truncate(egl_num_config as usize);
+
+	match system.platform {
+		// The GBM surface of a window uses the native visual id of the config as pixel format, which must be scanned out.
+		Platform::Kms(_) if surface_type == ffi::EGL_WINDOW_BIT => {
+			for egl_config in egl_configs {
+				let mut format: ffi::EGLint = 0;
+				if (system.lib_egl.eglGetConfigAttrib)(system.egl_display, egl_config, ffi::EGL_NATIVE_VISUAL_ID as ffi::EGLint, &mut format as *mut ffi::EGLint) == 0 { continue; }
+				if kms::is_scanout_format(format as libc::uint32_t) { return Ok(egl_config); }
+			}
+			Err(Error::Unsupported("no EGL config with a pixel format which can be scanned out"))
+		},
+		_ => egl_configs.first().cloned().ok_or(Error::Fn("eglChooseConfig")),
+	}
+}
 
+/// Create a GLES context of a version. The mutex of the system must be locked.
+unsafe fn create_context(system: &System, config: &WindowConfig, egl_config: ffi::EGLConfig, share_context: ffi::EGLContext, version: GlesVersion) -> Result<ffi::EGLContext, Error> {
+	let mut context_attributes: Vec<ffi::EGLint> = Vec::new();
+	if version == GlesVersion(2, 0) {
+		context_attributes.extend_from_slice(&[ffi::EGL_CONTEXT_CLIENT_VERSION as ffi::EGLint, 2]);
+	} else {
+		// EGL_KHR_create_context, part of EGL 1.5 with the same tokens.
+		if !system.has_egl_feature((1, 5), "EGL_KHR_create_context") { return Err(Error::Unsupported("EGL_KHR_create_context")); }
+		context_attributes.extend_from_slice(&[
+			ffi::EGL_CONTEXT_MAJOR_VERSION_KHR as ffi::EGLint, version.0 as ffi::EGLint,
+			ffi::EGL_CONTEXT_MINOR_VERSION_KHR as ffi::EGLint, version.1 as ffi::EGLint,
+		]);
+	}
+	if config.debug {
+		if system.egl_version >= (1, 5) {
+			context_attributes.extend_from_slice(&[ffi::EGL_CONTEXT_OPENGL_DEBUG as ffi::EGLint, ffi::EGL_TRUE as ffi::EGLint]);
+		} else if system.has_egl_extension("EGL_KHR_create_context") {
+			context_attributes.extend_from_slice(&[ffi::EGL_CONTEXT_FLAGS_KHR as ffi::EGLint, ffi::EGL_CONTEXT_OPENGL_DEBUG_BIT_KHR as ffi::EGLint]);
+		} else {
+			return Err(Error::Unsupported("EGL_KHR_create_context"));
+		}
+	}
+	let strategy = match config.robustness {
+		Robustness::NotRobust => None,
+		Robustness::NoResetNotification => Some(ffi::EGL_NO_RESET_NOTIFICATION_EXT),
+		Robustness::LoseContextOnReset => Some(ffi::EGL_LOSE_CONTEXT_ON_RESET_EXT),
+	};
+	match strategy {
+		Some(strategy) => {
+			// EGL_EXT_create_context_robustness, part of EGL 1.5 with other attribute tokens.
+			let (robust_access, reset_notification_strategy) = if system.has_egl_extension("EGL_EXT_create_context_robustness") {
+				(ffi::EGL_CONTEXT_OPENGL_ROBUST_ACCESS_EXT, ffi::EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_EXT)
+			} else if system.egl_version >= (1, 5) {
+				(ffi::EGL_CONTEXT_OPENGL_ROBUST_ACCESS, ffi::EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY)
+			} else {
+				return Err(Error::Unsupported("EGL_EXT_create_context_robustness"));
+			};
+			context_attributes.extend_from_slice(&[
+				robust_access as ffi::EGLint, ffi::EGL_TRUE as ffi::EGLint,
+				reset_notification_strategy as ffi::EGLint, strategy as ffi::EGLint,
+			]);
+		},
+		None => {},
+	}
+	context_attributes.push(ffi::EGL_NONE as ffi::EGLint);
+
+	let egl_context = (system.lib_egl.eglCreateContext)(system.egl_display, egl_config, share_context, context_attributes.as_ptr());
+	if egl_context == ffi::EGL_NO_CONTEXT { return Err(Error::Fn("eglCreateContext")); }
+	try!{gl_error(&system.lib_glesv2, "eglCreateContext")};
+
+	Ok(egl_context)
+}
+
+/// The extensions of an EGL display. Returns the client extensions for EGL_NO_DISPLAY.
+unsafe fn egl_extensions(lib_egl: &ffi::LibEGL, egl_display: ffi::EGLDisplay) -> Vec<String> {
+	let extensions = (lib_egl.eglQueryString)(egl_display, ffi::EGL_EXTENSIONS as ffi::EGLint);
+	if extensions.is_null() { return Vec::new(); }
+	CStr::from_ptr(extensions).to_string_lossy().split_whitespace().map(|e| { e.to_string() }).collect()
+}
+
+/// The native window an EGL surface is created from.
+enum NativeWindow {
+	/// Not created yet.
+	None,
+	/// Dispmanx element.
+	Dispmanx(dispmanx::Surface),
+	/// GBM surface.
+	Kms(kms::Surface),
+	/// Pbuffer of the size given.
+	Pbuffer(u32, u32),
+}
+
+/// The context and surfaces current before a window is made current. They are made current again by `restore`, or when dropped on a failure.
+struct PreviousCurrent<'a> {
+	lib_egl: &'a ffi::LibEGL,
+	egl_display: ffi::EGLDisplay,
+	egl_context: ffi::EGLContext,
+	draw_surface: ffi::EGLSurface,
+	read_surface: ffi::EGLSurface,
+	restored: bool,
+}
+impl<'a> PreviousCurrent<'a> {
+	unsafe fn get(lib_egl: &'a ffi::LibEGL, egl_display: ffi::EGLDisplay) -> Self {
+		PreviousCurrent {
+			lib_egl: lib_egl,
+			egl_display: egl_display,
+			egl_context: (lib_egl.eglGetCurrentContext)(),
+			draw_surface: (lib_egl.eglGetCurrentSurface)(ffi::EGL_DRAW as ffi::EGLint),
+			read_surface: (lib_egl.eglGetCurrentSurface)(ffi::EGL_READ as ffi::EGLint),
+			restored: false,
+		}
+	}
+	fn restore(&mut self) -> Result<(), Error> {
+		self.restored = true;
+		if unsafe { (self.lib_egl.eglMakeCurrent)(self.egl_display, self.draw_surface, self.read_surface, self.egl_context) } == 0 { return Err(Error::Fn("eglMakeCurrent")); }
+		Ok(())
+	}
+}
+impl<'a> Drop for PreviousCurrent<'a> {
+	fn drop(&mut self) {
+		if !self.restored { let _ = self.restore(); }
+	}
+}
 
 /// A (fullscreen) window.
 pub struct Window<S> where S: Deref<Target=System> {
@@ -248,164 +686,257 @@ pub struct Window<S> where S: Deref<Target=System> {
 	pub system: S,
 	/// EGL context.
 	egl_context: ffi::EGLContext,
-	/// Dispmanx display.
-	dispmanx_display: ffi::DispmanxDisplayHandle,
-	/// Egl-Dispmanx window.
-	egl_dispmanx_window: Box<ffi::EGLDispmanxWindow>,
+	/// The native window.
+	native_window: NativeWindow,
 	/// EGL surface.
 	egl_surface: ffi::EGLSurface,
+	/// OpenGL ES version of the context.
+	gles_version: GlesVersion,
 }
 impl<S> Window<S> where S: Deref<Target=System> {
 	/// Create a window.
 	pub fn new(system: S, config: &WindowConfig) -> Result<Self, Error> {
+		Window::create(system, config, false, ffi::EGL_NO_CONTEXT)
+	}
+	/// Create a window whose context shares textures, buffers and programs with the context of another window of the same system.
+	/// The context current on the calling thread stays current.
+	pub fn new_shared<T>(system: S, config: &WindowConfig, share: &Window<T>) -> Result<Self, Error> where T: Deref<Target=System> {
+		if &*system as *const System != &*share.system as *const System { return Err(Error::Unsupported("only windows of the same system can share contexts")); }
+		Window::create(system, config, false, share.egl_context)
+	}
+	/// Create an offscreen context rendering to a pbuffer of the surface size (1x1 if none), optionally sharing with the context of another window of the same system.
+	/// Useful to upload textures from a loader thread while another window renders. The context current on the calling thread stays current.
+	pub fn new_offscreen<T>(system: S, config: &WindowConfig, share: Option<&Window<T>>) -> Result<Self, Error> where T: Deref<Target=System> {
+		let share_context = match share {
+			Some(share) => {
+				if &*system as *const System != &*share.system as *const System { return Err(Error::Unsupported("only windows of the same system can share contexts")); }
+				share.egl_context
+			},
+			None => ffi::EGL_NO_CONTEXT,
+		};
+		let config = WindowConfig { surface_size: Some(config.surface_size.unwrap_or((1, 1))), .. *config };
+		Window::create(system, &config, true, share_context)
+	}
+	/// Create a window rendering to a native window or to a pbuffer, with a context sharing with the context given (may be EGL_NO_CONTEXT).
+	fn create(system: S, config: &WindowConfig, offscreen: bool, share_context: ffi::EGLContext) -> Result<Self, Error> {
+		let mut window = Window::empty(system);
+		try!(window.initialize(config, offscreen, share_context, None));
+		Ok(window)
+	}
+	/// A window without context and surface.
+	fn empty(system: S) -> Self {
+		Window {
+			system: system,
+			egl_context: 0 as ffi::EGLContext,
+			native_window: NativeWindow::None,
+			egl_surface: 0 as ffi::EGLSurface,
+			gles_version: GlesVersion(2, 0),
+		}
+	}
+	/// Create the context and the surface of an empty window. The element of a dispmanx window is added in the update given, else in an own update.
+	/// Whatever was created before a failure is destroyed when the window is dropped.
+	fn initialize(&mut self, config: &WindowConfig, offscreen: bool, share_context: ffi::EGLContext, update: Option<ffi::DispmanxUpdateHandle>) -> Result<(), Error> {
+		let window = self;
 		unsafe {
-			let mut window = Window {
-				system: system,
-				egl_context: 0 as ffi::EGLContext,
-				dispmanx_display: ffi::DISPMANX_NO_HANDLE,
-				egl_dispmanx_window: Box::new(ffi::EGLDispmanxWindow {
-					element: ffi::DISPMANX_NO_HANDLE,
-					width: 0,
-					height: 0,
-				}),
-				egl_surface: 0 as ffi::EGLSurface,
-			};
-
 			{
 				// Lock the mutex
-				let _ = window.system.mutex.lock();
+				let _guard = window.system.mutex.lock().unwrap();
 		
+				// Shared and offscreen windows are usually created while another window renders on this thread. Its context is made current again afterwards.
+				let restore_current = offscreen || share_context != ffi::EGL_NO_CONTEXT;
+
 				// Choose a EGL-config
-				let egl_config = {
-					let mut attribute_list: [ffi::EGLint; 13] = [
-						ffi::EGL_SURFACE_TYPE as ffi::EGLint, ffi::EGL_WINDOW_BIT as ffi::EGLint,
-						ffi::EGL_RED_SIZE as ffi::EGLint, config.red.0 as ffi::EGLint,
-						ffi::EGL_GREEN_SIZE as ffi::EGLint, config.green.0 as ffi::EGLint,
-						ffi::EGL_BLUE_SIZE as ffi::EGLint, config.blue.0 as ffi::EGLint,
-						ffi::EGL_NONE as ffi::EGLint, ffi::EGL_NONE as ffi::EGLint,
-						ffi::EGL_NONE as ffi::EGLint, ffi::EGL_NONE as ffi::EGLint,
-						ffi::EGL_NONE as ffi::EGLint,
-					];
-					let mut attribute_list_size = 9;
-					match config.alpha.as_ref() {
-						Some(alpha) => {
-							attribute_list[attribute_list_size + 0] = ffi::EGL_ALPHA_SIZE as ffi::EGLint;
-							attribute_list[attribute_list_size + 1] = alpha.0 as ffi::EGLint;
-							attribute_list_size += 2;
-						},
-						None => {},
-					}
-					match config.depth.as_ref() {
-						Some(depth) => {
-							attribute_list[attribute_list_size + 0] = ffi::EGL_DEPTH_SIZE as ffi::EGLint;
-							attribute_list[attribute_list_size + 1] = depth.0 as ffi::EGLint;
-						},
-						None => {},
-					}
-					let mut egl_config: ffi::EGLConfig = 0 as ffi::EGLConfig;
-					let mut egl_num_config: ffi::EGLint = 1;
-					if (window.system.lib_egl.eglChooseConfig)(window.system.egl_display, &attribute_list as *const ffi::EGLint, &mut egl_config as *mut ffi::EGLConfig, 1, &mut egl_num_config as *mut ffi::EGLint) == 0 { return Err(Error::Fn("eglChooseConfig")); }
-					try!{gl_error(&window.system.lib_glesv2, "eglInitialize")};
-			
-					egl_config
+				let offscreen = match window.system.platform {
+					Platform::Surfaceless => true,
+					_ => offscreen,
 				};
+				let surface_type = if offscreen { ffi::EGL_PBUFFER_BIT } else { ffi::EGL_WINDOW_BIT };
 
 				// Bind GLES api.
 				if (window.system.lib_egl.eglBindAPI)(ffi::EGL_OPENGL_ES_API) == 0 { return Err(Error::Fn("eglBindAPI")); }
 				try!{gl_error(&window.system.lib_glesv2, "eglBindAPI")};
 
-				// Create a GLES context with client version 2. 
-				let context_attributes: [ffi::EGLint; 3] = [
-					ffi::EGL_CONTEXT_CLIENT_VERSION as ffi::EGLint, 2,
-					ffi::EGL_NONE as ffi::EGLint
-				];
-				window.egl_context = (window.system.lib_egl.eglCreateContext)(window.system.egl_display, egl_config, ffi::EGL_NO_CONTEXT, &context_attributes as *const ffi::EGLint);
-				if window.egl_context == ffi::EGL_NO_CONTEXT { return Err(Error::Fn("eglCreateContext")); }
-				try!{gl_error(&window.system.lib_glesv2, "eglCreateContext")};
-
-				// Get the size of the display.
-				let (dest_width, dest_height) = try!(window.system.display_size_no_lock(config.display));
-				// The selected surface size.
-				let (src_width, src_height) = config.surface_size.unwrap_or((dest_width, dest_height));
-		
-				window.dispmanx_display = (window.system.lib_bcm_host.vc_dispmanx_display_open)(0);
-				if window.dispmanx_display == ffi::DISPMANX_NO_HANDLE { return Err(Error::Fn("vc_dispmanx_display_open")); } 
-				let dispmanx_update = (window.system.lib_bcm_host.vc_dispmanx_update_start)(0);
-				if dispmanx_update == ffi::DISPMANX_NO_HANDLE { return Err(Error::Fn("vc_dispmanx_update_start")); } 
-				let dispmanx_element = {
-					let src_rect = ffi::VcRect {
-						x: 0,
-						y: 0,
-						width: src_width as libc::int32_t,
-						height: src_height as libc::int32_t,
-					};
-					let dest_rect = ffi::VcRect {
-						x: 0,
-						y: 0,
-						width: (dest_width << 16) as libc::int32_t,
-						height: (dest_height << 16) as libc::int32_t,
-					};
-					(window.system.lib_bcm_host.vc_dispmanx_element_add)(
-						dispmanx_update,
-						window.dispmanx_display,
-						0, &dest_rect as *const ffi::VcRect,
-						0, &src_rect as *const ffi::VcRect,
-						ffi::DISPMANX_PROTECTION_NONE,
-						0 as *mut ffi::VcDispmanxAlpha,
-						0 as *mut ffi::DispmanxClamp,
-						0
-					)
+				// Create a GLES context of the highest version possible.
+				let mut result = Err(Error::Unsupported("no OpenGL ES version in the requested range"));
+				for version in config.gles_version.versions() {
+					result = choose_config(&window.system, config, surface_type, version).and_then(|egl_config| {
+						create_context(&window.system, config, egl_config, share_context, version).map(|egl_context| { (egl_config, egl_context, version) })
+					});
+					if result.is_ok() { break; }
+				}
+				let (egl_config, egl_context, version) = try!(result);
+				window.egl_context = egl_context;
+				window.gles_version = version;
+
+				// Create the native window. Elements added before a failure are removed when the window is dropped.
+				let mut shown = Ok(());
+				window.native_window = match window.system.platform {
+					_ if offscreen => {
+						let (width, height) = match config.surface_size {
+							Some(size) => size,
+							None => return Err(Error::Unsupported("the surfaceless platform requires a surface size")),
+						};
+						NativeWindow::Pbuffer(width, height)
+					},
+					Platform::Dispmanx(ref lib_bcm_host) => {
+						// Get the size of the display.
+						let display_size = try!(window.system.display_size_no_lock(config.display));
+						// The selected surface size.
+						let surface_size = config.surface_size.unwrap_or(display_size);
+						let overscan = match config.overscan {
+							Some(overscan) => overscan,
+							None => window.system.firmware_overscan_no_lock().unwrap_or(Overscan::default()),
+						};
+						let mut surface = try!(dispmanx::Surface::new(lib_bcm_host, display_size, surface_size, overscan, config));
+						shown = match update {
+							Some(update) => surface.add_elements(lib_bcm_host, update, config),
+							None => {
+								let update = (lib_bcm_host.vc_dispmanx_update_start)(0);
+								if update == ffi::DISPMANX_NO_HANDLE { surface.close(lib_bcm_host); return Err(Error::Fn("vc_dispmanx_update_start")); }
+								let result = surface.add_elements(lib_bcm_host, update, config);
+								if (lib_bcm_host.vc_dispmanx_update_submit_sync)(update) != ffi::DISPMANX_SUCCESS && result.is_ok() { Err(Error::Fn("vc_dispmanx_update_submit_sync")) } else { result }
+							},
+						};
+						NativeWindow::Dispmanx(surface)
+					},
+					Platform::Kms(ref device) => {
+						// The GBM surface must use the pixel format of the EGL config.
+						let mut format: ffi::EGLint = 0;
+						if (window.system.lib_egl.eglGetConfigAttrib)(window.system.egl_display, egl_config, ffi::EGL_NATIVE_VISUAL_ID as ffi::EGLint, &mut format as *mut ffi::EGLint) == 0 { return Err(Error::Fn("eglGetConfigAttrib")); }
+						let output = try!(device.output(config.display, config.surface_size));
+						let surface = try!(kms::Surface::new(device, output, format as libc::uint32_t));
+						NativeWindow::Kms(surface)
+					},
+					Platform::Surfaceless => unreachable!(),
 				};
-				if dispmanx_element == ffi::DISPMANX_NO_HANDLE { return Err(Error::Fn("vc_dispmanx_element_add")); }
-				if (window.system.lib_bcm_host.vc_dispmanx_update_submit_sync)(dispmanx_update) != ffi::DISPMANX_SUCCESS { return Err(Error::Fn("vc_dispmanx_update_submit_sync")); }
-				try!{gl_error(&window.system.lib_glesv2, "vc_dispmanx_update_submit_sync")};
-
-				window.egl_dispmanx_window.element = dispmanx_element;
-				window.egl_dispmanx_window.width = src_width as libc::c_int;
-				window.egl_dispmanx_window.height = src_height as libc::c_int;
+				try!(shown);
+				match window.native_window {
+					NativeWindow::Dispmanx(..) => try!{gl_error(&window.system.lib_glesv2, "vc_dispmanx_update_submit_sync")},
+					_ => {},
+				}
 
-				window.egl_surface = (window.system.lib_egl.eglCreateWindowSurface)(window.system.egl_display, egl_config, window.egl_dispmanx_window.as_ref() as ffi::EGLNativeWindowType, 0 as *const ffi::EGLint);
-				if window.egl_surface == ffi::EGL_NO_SURFACE { return Err(Error::Fn("eglCreateWindowSurface")); }
-				try!{gl_error(&window.system.lib_glesv2, "eglCreateWindowSurface")};
+				let native_window = match window.native_window {
+					NativeWindow::Dispmanx(ref surface) => Some(surface.native_window()),
+					NativeWindow::Kms(ref surface) => Some(surface.native_window()),
+					NativeWindow::Pbuffer(..) => None,
+					NativeWindow::None => unreachable!(),
+				};
+				match native_window {
+					Some(native_window) => {
+						window.egl_surface = (window.system.lib_egl.eglCreateWindowSurface)(window.system.egl_display, egl_config, native_window, 0 as *const ffi::EGLint);
+						if window.egl_surface == ffi::EGL_NO_SURFACE { return Err(Error::Fn("eglCreateWindowSurface")); }
+						try!{gl_error(&window.system.lib_glesv2, "eglCreateWindowSurface")};
+					},
+					None => {
+						let (width, height) = window.get_framebuffer_dimensions();
+						let pbuffer_attributes: [ffi::EGLint; 5] = [
+							ffi::EGL_WIDTH as ffi::EGLint, width as ffi::EGLint,
+							ffi::EGL_HEIGHT as ffi::EGLint, height as ffi::EGLint,
+							ffi::EGL_NONE as ffi::EGLint
+						];
+						window.egl_surface = (window.system.lib_egl.eglCreatePbufferSurface)(window.system.egl_display, egl_config, &pbuffer_attributes as *const ffi::EGLint);
+						if window.egl_surface == ffi::EGL_NO_SURFACE { return Err(Error::Fn("eglCreatePbufferSurface")); }
+						try!{gl_error(&window.system.lib_glesv2, "eglCreatePbufferSurface")};
+					},
+				}
 		
+				let mut previous = if restore_current { Some(PreviousCurrent::get(&window.system.lib_egl, window.system.egl_display)) } else { None };
 				if (window.system.lib_egl.eglMakeCurrent)(window.system.egl_display, window.egl_surface, window.egl_surface, window.egl_context) == 0 { return Err(Error::Fn("eglMakeCurrent")); }
 				try!{gl_error(&window.system.lib_glesv2, "eglMakeCurrent")};
+
+				match config.swap_interval {
+					Some(interval) => {
+						if (window.system.lib_egl.eglSwapInterval)(window.system.egl_display, interval as ffi::EGLint) == 0 { return Err(Error::Fn("eglSwapInterval")); }
+						try!{gl_error(&window.system.lib_glesv2, "eglSwapInterval")};
+					},
+					None => {},
+				}
+
+				// The implementation may provide a higher version than requested.
+				let gl_version = (window.system.lib_glesv2.glGetString)(ffi::GL_VERSION);
+				if !gl_version.is_null() {
+					let gl_version = CStr::from_ptr(gl_version as *const libc::c_char).to_string_lossy();
+					window.gles_version = GlesVersion::parse(&gl_version).unwrap_or(window.gles_version);
+				}
+
+				match previous {
+					Some(ref mut previous) => try!(previous.restore()),
+					None => {},
+				}
 			}
 			
-			Ok(window)
+			Ok(())
+		}
+	}
+}
+impl<S> Window<S> where S: Deref<Target=System> {
+	/// The OpenGL ES version of the context.
+	pub fn gles_version(&self) -> GlesVersion {
+		self.gles_version
+	}
+	/// The mapping between the surface and the display, e.g. to convert touch positions. Identity except for scaled dispmanx surfaces.
+	pub fn surface_mapping(&self) -> SurfaceMapping {
+		match self.native_window {
+			NativeWindow::Dispmanx(ref surface) => surface.mapping(),
+			_ => SurfaceMapping::identity(self.get_framebuffer_dimensions()),
+		}
+	}
+	/// The part of the framebuffer which is visible on the display, inside of the overscan and not cropped by the scaling.
+	/// The origin is at the bottom left like for a glium viewport or scissor. The whole framebuffer except for dispmanx.
+	pub fn safe_area(&self) -> glium::Rect {
+		let (width, height) = self.get_framebuffer_dimensions();
+		let (mapping, visible) = match self.native_window {
+			NativeWindow::Dispmanx(ref surface) => (surface.mapping(), surface.safe_area()),
+			_ => return glium::Rect { left: 0, bottom: 0, width: width, height: height },
+		};
+		let area = mapping.surface_area(visible, (width, height));
+		if area.width == 0 || area.height == 0 { return glium::Rect { left: 0, bottom: 0, width: 0, height: 0 }; }
+		glium::Rect {
+			left: area.x as u32,
+			bottom: height - (area.y as u32 + area.height),
+			width: area.width,
+			height: area.height,
 		}
 	}
 }
 impl<S> Drop for Window<S> where S: Deref<Target=System> {
 	fn drop(&mut self) {
-		let _ = self.system.mutex.lock();
+		let was_current = self.is_current();
+		let _guard = self.system.mutex.lock().unwrap();
 		unsafe {
-			if self.egl_surface != ffi::EGL_NO_SURFACE {
+			if was_current {
 				assert!((self.system.lib_egl.eglMakeCurrent)(self.system.egl_display, ffi::EGL_NO_SURFACE, ffi::EGL_NO_SURFACE, ffi::EGL_NO_CONTEXT) != 0);
+			}
+			if self.egl_surface != ffi::EGL_NO_SURFACE {
 				assert!((self.system.lib_egl.eglDestroySurface)(self.system.egl_display, self.egl_surface) != 0);
 				self.egl_surface = ffi::EGL_NO_SURFACE;
 			}
-			if self.egl_dispmanx_window.element != ffi::DISPMANX_NO_HANDLE {
-				let update = (self.system.lib_bcm_host.vc_dispmanx_update_start)(0);
-				assert!(update != ffi::DISPMANX_NO_HANDLE);
-				assert!((self.system.lib_bcm_host.vc_dispmanx_element_remove)(update, self.egl_dispmanx_window.element) == ffi::DISPMANX_SUCCESS);
-				assert!((self.system.lib_bcm_host.vc_dispmanx_update_submit_sync)(update) == ffi::DISPMANX_SUCCESS);
-				self.egl_dispmanx_window.element = ffi::DISPMANX_NO_HANDLE;
-			}
-			if self.dispmanx_display != ffi::DISPMANX_NO_HANDLE {
-				assert!((self.system.lib_bcm_host.vc_dispmanx_display_close)(self.dispmanx_display) == ffi::DISPMANX_SUCCESS);
-				self.dispmanx_display = ffi::DISPMANX_NO_HANDLE;
+			match (&mut self.native_window, &self.system.platform) {
+				(&mut NativeWindow::Dispmanx(ref mut surface), &Platform::Dispmanx(ref lib_bcm_host)) => surface.destroy(lib_bcm_host),
+				(&mut NativeWindow::Kms(ref mut surface), &Platform::Kms(ref device)) => surface.destroy(device),
+				_ => {},
 			}
+			self.native_window = NativeWindow::None;
 			if self.egl_context != ffi::EGL_NO_CONTEXT {
 				assert!((self.system.lib_egl.eglDestroyContext)(self.system.egl_display, self.egl_context) != 0);
 				self.egl_context = ffi::EGL_NO_CONTEXT;
 			}
+			if was_current {
+				(self.system.lib_egl.eglReleaseThread)();
+			}
 		}
 	}
 }
 unsafe impl<S> glium::backend::Backend for Window<S> where S: Deref<Target=System> {
 	fn swap_buffers(&self) -> Result<(), glium::SwapBuffersError> {
 		if unsafe { (self.system.lib_egl.eglSwapBuffers)(self.system.egl_display, self.egl_surface) } == 0 { panic!("eglSwapBuffers failed"); }
+		match (&self.native_window, &self.system.platform) {
+			(&NativeWindow::Kms(ref surface), &Platform::Kms(ref device)) => {
+				// glium has no error for a failed page flip. The surface can not be shown anymore, like after a lost context.
+				if unsafe { surface.present(device) }.is_err() { return Err(glium::SwapBuffersError::ContextLost); }
+			},
+			_ => {},
+		}
 		Ok(())
 	}
 	unsafe fn get_proc_address(&self, symbol: &str) -> *const std::os::raw::c_void {
@@ -416,8 +947,12 @@ unsafe impl<S> glium::backend::Backend for Window<S> where S: Deref<Target=Syste
 		}
 	}
 	fn get_framebuffer_dimensions(&self) -> (u32, u32) {
-		let win = self.egl_dispmanx_window.as_ref();
-		(win.width as u32, win.height as u32)
+		match self.native_window {
+			NativeWindow::Dispmanx(ref surface) => surface.dimensions(),
+			NativeWindow::Kms(ref surface) => surface.dimensions(),
+			NativeWindow::Pbuffer(width, height) => (width, height),
+			NativeWindow::None => (0, 0),
+		}
 	}
 	fn is_current(&self) -> bool {
 		unsafe { (self.system.lib_egl.eglGetCurrentContext)() == self.egl_context }
@@ -427,9 +962,33 @@ unsafe impl<S> glium::backend::Backend for Window<S> where S: Deref<Target=Syste
 		if (self.system.lib_egl.eglMakeCurrent)(self.system.egl_display, self.egl_surface, self.egl_surface, self.egl_context) == 0 { panic!("eglMakeCurrent failed"); }
 	}
 }
-/// Creates a new glium facade.
-pub fn create_window_facade(system: &Arc<System>, config: &WindowConfig) -> Result<Rc<glium::backend::Context>, glium::GliumCreationError<Error>> {
-	let window = Rc::new(try!(Window::new(system.clone(), config).map_err(|e| { glium::GliumCreationError::BackendCreationError(e) })));
-	unsafe { glium::backend::Context::new::<Rc<Window<Arc<System>>>, Error>(window, true, Default::default()) }
+impl<S> Window<S> where S: Deref<Target=System> {
+	/// Release the context from the calling thread so that the window can be moved to another thread.
+	pub fn into_handle(self) -> WindowHandle<S> {
+		if self.is_current() {
+			let _guard = self.system.mutex.lock().unwrap();
+			unsafe {
+				assert!((self.system.lib_egl.eglMakeCurrent)(self.system.egl_display, ffi::EGL_NO_SURFACE, ffi::EGL_NO_SURFACE, ffi::EGL_NO_CONTEXT) != 0);
+				(self.system.lib_egl.eglReleaseThread)();
+			}
+		}
+		WindowHandle(self)
+	}
 }
 
+/// A window whose context is not current on any thread. Unlike `Window` it can be sent to another thread.
+pub struct WindowHandle<S>(Window<S>) where S: Deref<Target=System>;
+impl<S> WindowHandle<S> where S: Deref<Target=System> {
+	/// Make the context current on the calling thread.
+	pub fn into_window(self) -> Result<Window<S>, Error> {
+		let window = self.0;
+		{
+			let _guard = window.system.mutex.lock().unwrap();
+			unsafe {
+				if (window.system.lib_egl.eglMakeCurrent)(window.system.egl_display, window.egl_surface, window.egl_surface, window.egl_context) == 0 { return Err(Error::Fn("eglMakeCurrent")); }
+			}
+		}
+		Ok(window)
+	}
+}
+unsafe impl<S> Send for WindowHandle<S> where S: Deref<Target=System> + Send {}
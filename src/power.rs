@@ -0,0 +1,177 @@
+use std::mem;
+use std::sync::Arc;
+
+use libc;
+
+use ffi;
+use error::Error;
+use config::{Display, Rect, FixedRect};
+use dispmanx;
+use super::System;
+
+/// Layer used for blanking elements. Above any window.
+const BLANK_LAYER: libc::int32_t = 0x7FFFFFFF;
+
+/// A black fullscreen dispmanx element used to blank a display. Removed when dropped, which must happen while the mutex of the system is locked.
+pub struct BlankLayer {
+	/// The Bcm-Host library of the system.
+	lib_bcm_host: Arc<ffi::LibBcmHost>,
+	/// The display which is blanked.
+	display: Display,
+	/// Dispmanx display.
+	dispmanx_display: ffi::DispmanxDisplayHandle,
+	/// Dispmanx resource holding a single black pixel.
+	resource: ffi::DispmanxResourceHandle,
+	/// Dispmanx element.
+	element: ffi::DispmanxElementHandle,
+}
+impl BlankLayer {
+	/// Create a blanking element. The mutex of the system must be locked.
+	unsafe fn new(system: &System, lib_bcm_host: &Arc<ffi::LibBcmHost>, display: Display) -> Result<Self, Error> {
+		let mut layer = BlankLayer {
+			lib_bcm_host: lib_bcm_host.clone(),
+			display: display,
+			dispmanx_display: ffi::DISPMANX_NO_HANDLE,
+			resource: ffi::DISPMANX_NO_HANDLE,
+			element: ffi::DISPMANX_NO_HANDLE,
+		};
+		let display_size = try!(system.display_size_no_lock(display));
+
+		// A single black pixel which gets scaled to the whole display.
+		layer.resource = try!(dispmanx::color_resource(lib_bcm_host, (0, 0, 0)));
+
+		layer.dispmanx_display = (lib_bcm_host.vc_dispmanx_display_open)(display.index() as libc::uint32_t);
+		if layer.dispmanx_display == ffi::DISPMANX_NO_HANDLE { return Err(Error::Fn("vc_dispmanx_display_open")); }
+
+		let update = (lib_bcm_host.vc_dispmanx_update_start)(0);
+		if update == ffi::DISPMANX_NO_HANDLE { return Err(Error::Fn("vc_dispmanx_update_start")); }
+		let element = dispmanx::add_element(lib_bcm_host, update, layer.dispmanx_display, BLANK_LAYER, layer.resource, FixedRect::from(Rect::from_size((1, 1))), Rect::from_size(display_size));
+		let submitted = (lib_bcm_host.vc_dispmanx_update_submit_sync)(update) == ffi::DISPMANX_SUCCESS;
+		layer.element = try!(element);
+		if !submitted { return Err(Error::Fn("vc_dispmanx_update_submit_sync")); }
+
+		Ok(layer)
+	}
+}
+impl Drop for BlankLayer {
+	fn drop(&mut self) {
+		let lib_bcm_host = &*self.lib_bcm_host;
+		unsafe {
+			if self.element != ffi::DISPMANX_NO_HANDLE {
+				let update = (lib_bcm_host.vc_dispmanx_update_start)(0);
+				assert!(update != ffi::DISPMANX_NO_HANDLE);
+				assert!((lib_bcm_host.vc_dispmanx_element_remove)(update, self.element) == ffi::DISPMANX_SUCCESS);
+				assert!((lib_bcm_host.vc_dispmanx_update_submit_sync)(update) == ffi::DISPMANX_SUCCESS);
+				self.element = ffi::DISPMANX_NO_HANDLE;
+			}
+			if self.dispmanx_display != ffi::DISPMANX_NO_HANDLE {
+				assert!((lib_bcm_host.vc_dispmanx_display_close)(self.dispmanx_display) == ffi::DISPMANX_SUCCESS);
+				self.dispmanx_display = ffi::DISPMANX_NO_HANDLE;
+			}
+			if self.resource != ffi::DISPMANX_NO_HANDLE {
+				assert!((lib_bcm_host.vc_dispmanx_resource_delete)(self.resource) == ffi::DISPMANX_SUCCESS);
+				self.resource = ffi::DISPMANX_NO_HANDLE;
+			}
+		}
+	}
+}
+
+/// Display power management.
+///
+/// Only supported on the dispmanx platform.
+/// Neither powering off Hdmi nor blanking touches any `Window`. The EGL surfaces and contexts stay valid,
+/// so rendering can continue right after the display is powered on or unblanked again.
+impl System {
+	/// Power off the Hdmi output (and any analog output).
+	pub fn hdmi_power_off(&self) -> Result<(), Error> {
+		let lib_bcm_host = try!(self.lib_bcm_host());
+		let _guard = self.mutex.lock().unwrap();
+		let mut tv_state: ffi::TvDisplayState = unsafe { mem::zeroed() };
+		if unsafe { (lib_bcm_host.vc_tv_get_display_state)(&mut tv_state as *mut ffi::TvDisplayState) } != 0 { return Err(Error::Fn("vc_tv_get_display_state")); }
+		if unsafe { (lib_bcm_host.vc_tv_power_off)() } != 0 { return Err(Error::Fn("vc_tv_power_off")); }
+		// Remember the analog output, so that it can be powered on again.
+		match analog_output(&tv_state) {
+			Some(sdtv) => *self.analog_state.lock().unwrap() = Some(sdtv),
+			None => {},
+		}
+		Ok(())
+	}
+	/// Power on the Hdmi output using the preferred mode of the connected display.
+	/// If the analog output was on when `hdmi_power_off` was called, it is powered on again with its previous mode instead.
+	pub fn hdmi_power_on(&self) -> Result<(), Error> {
+		let lib_bcm_host = try!(self.lib_bcm_host());
+		let _guard = self.mutex.lock().unwrap();
+		let mut analog_state = self.analog_state.lock().unwrap();
+		match *analog_state {
+			Some(sdtv) => {
+				if unsafe { (lib_bcm_host.vc_tv_sdtv_power_on)(sdtv.mode, &sdtv.display_options as *const ffi::SdtvOptions) } != 0 { return Err(Error::Fn("vc_tv_sdtv_power_on")); }
+			},
+			None => {
+				if unsafe { (lib_bcm_host.vc_tv_hdmi_power_on_preferred)() } != 0 { return Err(Error::Fn("vc_tv_hdmi_power_on_preferred")); }
+			},
+		}
+		*analog_state = None;
+		Ok(())
+	}
+	/// Blank a display by putting a black element on top of everything. Does nothing if the display is already blanked.
+	pub fn blank(&self, display: Display) -> Result<(), Error> {
+		let lib_bcm_host = try!(self.lib_bcm_host());
+		let _guard = self.mutex.lock().unwrap();
+		let mut blank_layers = self.blank_layers.lock().unwrap();
+		if blank_layers.iter().any(|l| { l.display == display }) { return Ok(()); }
+		let layer = try!(unsafe { BlankLayer::new(self, lib_bcm_host, display) });
+		blank_layers.push(layer);
+		Ok(())
+	}
+	/// Remove the blanking of a display. Does nothing if the display is not blanked.
+	pub fn unblank(&self, display: Display) {
+		let _guard = self.mutex.lock().unwrap();
+		let mut blank_layers = self.blank_layers.lock().unwrap();
+		match blank_layers.iter().position(|l| { l.display == display }) {
+			Some(index) => { blank_layers.remove(index); },
+			None => {},
+		}
+	}
+	/// Whether a display is currently blanked.
+	pub fn is_blanked(&self, display: Display) -> bool {
+		self.blank_layers.lock().unwrap().iter().any(|l| { l.display == display })
+	}
+}
+
+// The analog output to power on again instead of Hdmi, given the state of the TV service before powering off.
+// None if the analog output is not active, e.g. only attached or unplugged.
+fn analog_output(tv_state: &ffi::TvDisplayState) -> Option<ffi::SdtvDisplayState> {
+	if tv_state.state & (ffi::VC_SDTV_NTSC | ffi::VC_SDTV_PAL) != 0 { Some(tv_state.sdtv) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+	use std::mem;
+
+	use ffi;
+	use super::analog_output;
+
+	fn tv_state(state: u32) -> ffi::TvDisplayState {
+		let mut tv_state: ffi::TvDisplayState = unsafe { mem::zeroed() };
+		tv_state.state = state;
+		tv_state.sdtv.mode = state & (ffi::VC_SDTV_NTSC | ffi::VC_SDTV_PAL);
+		tv_state
+	}
+
+	#[test]
+	fn hdmi_is_restored() {
+		// Hdmi attached and active (VC_HDMI_ATTACHED | VC_HDMI_HDMI).
+		assert!(analog_output(&tv_state(0x0000_0006)).is_none());
+		// Hdmi active with the composite output unplugged or attached but off.
+		assert!(analog_output(&tv_state(0x0001_0006)).is_none());
+		assert!(analog_output(&tv_state(0x0002_0006)).is_none());
+		assert!(analog_output(&tv_state(0x0001_0000)).is_none());
+	}
+
+	#[test]
+	fn analog_is_restored() {
+		// Composite attached and active in NTSC or PAL.
+		assert_eq!(analog_output(&tv_state(0x0006_0000)).map(|s| { s.mode }), Some(ffi::VC_SDTV_NTSC));
+		assert_eq!(analog_output(&tv_state(0x000A_0000)).map(|s| { s.mode }), Some(ffi::VC_SDTV_PAL));
+	}
+}
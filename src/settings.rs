@@ -0,0 +1,189 @@
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use toml;
+
+use error::Error;
+use config::{LibDir, Display, Scaling, ColorBits, DepthBits, WindowConfig};
+
+/// Settings of a site: the library directory and the window configuration.
+///
+/// Loaded from a TOML file, e.g.
+///
+/// ```toml
+/// lib_dir = "/opt/vc/lib"
+///
+/// [window]
+/// display = "hdmi"
+/// surface_size = [1280, 720]
+/// alpha = 8
+/// depth = 24
+/// ```
+///
+/// Missing values keep their defaults. The following environment variables override the file:
+/// `GLIUM_PIB_LIB_DIR`, `GLIUM_PIB_DISPLAY` (hdmi, analog or lcd), `GLIUM_PIB_SURFACE_SIZE` (e.g. 1280x720),
+/// `GLIUM_PIB_SCALING` (stretch, fit, fill, integer or center),
+/// `GLIUM_PIB_RED_BITS`, `GLIUM_PIB_GREEN_BITS`, `GLIUM_PIB_BLUE_BITS`, `GLIUM_PIB_ALPHA_BITS` and `GLIUM_PIB_DEPTH_BITS`
+/// (0 disables the alpha channel or the depth buffer).
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+	/// The directory where the libraries are stored.
+	pub lib_dir: LibDir,
+	/// The window configuration.
+	pub window: WindowConfig,
+}
+impl Settings {
+	/// Load the settings from a TOML file and apply the environment variables.
+	pub fn load<P>(path: P) -> Result<Self, Error> where P: AsRef<Path> {
+		let mut text = String::new();
+		try!(File::open(path).and_then(|mut f| { f.read_to_string(&mut text) }).map_err(|e| { Error::Io(e) }));
+		let mut settings = try!(Settings::from_toml(&text));
+		try!(settings.apply_env());
+		Ok(settings)
+	}
+	/// The default settings with the environment variables applied.
+	pub fn from_env() -> Result<Self, Error> {
+		let mut settings = Settings::default();
+		try!(settings.apply_env());
+		Ok(settings)
+	}
+	/// Parse settings from TOML. Does not apply the environment variables.
+	pub fn from_toml(text: &str) -> Result<Self, Error> {
+		toml::from_str(text).map_err(|e| { Error::Config(e.to_string()) })
+	}
+	/// Serialize the settings to TOML.
+	pub fn to_toml(&self) -> Result<String, Error> {
+		toml::to_string(self).map_err(|e| { Error::Config(e.to_string()) })
+	}
+	/// Override the settings with the environment variables which are set.
+	pub fn apply_env(&mut self) -> Result<(), Error> {
+		if let Some(lib_dir) = env::var_os("GLIUM_PIB_LIB_DIR") {
+			self.lib_dir = LibDir(PathBuf::from(lib_dir));
+		}
+		if let Some(display) = try!(var("GLIUM_PIB_DISPLAY")) {
+			self.window.display = match display.to_lowercase().as_str() {
+				"hdmi" => Display::Hdmi,
+				"analog" => Display::Analog,
+				"lcd" => Display::Lcd,
+				_ => return Err(Error::Config(format!("GLIUM_PIB_DISPLAY: unknown display {}", display))),
+			};
+		}
+		if let Some(scaling) = try!(var("GLIUM_PIB_SCALING")) {
+			self.window.scaling = match scaling.to_lowercase().as_str() {
+				"stretch" => Scaling::Stretch,
+				"fit" => Scaling::Fit,
+				"fill" => Scaling::Fill,
+				"integer" => Scaling::Integer,
+				"center" => Scaling::Center,
+				_ => return Err(Error::Config(format!("GLIUM_PIB_SCALING: unknown scaling {}", scaling))),
+			};
+		}
+		if let Some(size) = try!(var("GLIUM_PIB_SURFACE_SIZE")) {
+			let mut parts = size.splitn(2, 'x');
+			self.window.surface_size = match (parts.next().and_then(|w| { w.trim().parse().ok() }), parts.next().and_then(|h| { h.trim().parse().ok() })) {
+				(Some(width), Some(height)) => Some((width, height)),
+				_ => return Err(Error::Config(format!("GLIUM_PIB_SURFACE_SIZE: expected WIDTHxHEIGHT, got {}", size))),
+			};
+		}
+		if let Some(bits) = try!(parse_var::<u32>("GLIUM_PIB_RED_BITS")) { self.window.red = ColorBits(bits); }
+		if let Some(bits) = try!(parse_var::<u32>("GLIUM_PIB_GREEN_BITS")) { self.window.green = ColorBits(bits); }
+		if let Some(bits) = try!(parse_var::<u32>("GLIUM_PIB_BLUE_BITS")) { self.window.blue = ColorBits(bits); }
+		if let Some(bits) = try!(parse_var::<u32>("GLIUM_PIB_ALPHA_BITS")) {
+			self.window.alpha = if bits == 0 { None } else { Some(ColorBits(bits)) };
+		}
+		if let Some(bits) = try!(parse_var::<u32>("GLIUM_PIB_DEPTH_BITS")) {
+			self.window.depth = if bits == 0 { None } else { Some(DepthBits(bits)) };
+		}
+		Ok(())
+	}
+}
+
+// Read an environment variable. None if it is not set.
+fn var(name: &'static str) -> Result<Option<String>, Error> {
+	match env::var(name) {
+		Ok(value) => Ok(Some(value)),
+		Err(env::VarError::NotPresent) => Ok(None),
+		Err(env::VarError::NotUnicode(_)) => Err(Error::Config(format!("{}: not valid unicode", name))),
+	}
+}
+
+// Read and parse an environment variable. None if it is not set.
+fn parse_var<T>(name: &'static str) -> Result<Option<T>, Error> where T: FromStr {
+	match try!(var(name)) {
+		Some(value) => value.trim().parse().map(Some).map_err(|_| { Error::Config(format!("{}: invalid value {}", name, value)) }),
+		None => Ok(None),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+	use std::path::Path;
+
+	use super::*;
+	use config::{Display, Scaling};
+
+	const VARS: [&'static str; 9] = [
+		"GLIUM_PIB_LIB_DIR", "GLIUM_PIB_DISPLAY", "GLIUM_PIB_SURFACE_SIZE", "GLIUM_PIB_SCALING",
+		"GLIUM_PIB_RED_BITS", "GLIUM_PIB_GREEN_BITS", "GLIUM_PIB_BLUE_BITS", "GLIUM_PIB_ALPHA_BITS", "GLIUM_PIB_DEPTH_BITS",
+	];
+
+	#[test]
+	fn parse_toml() {
+		let settings = Settings::from_toml("lib_dir = \"/usr/lib\"\n\n[window]\ndisplay = \"lcd\"\nsurface_size = [1280, 720]\nalpha = 8\n").unwrap();
+		assert_eq!(&*settings.lib_dir, Path::new("/usr/lib"));
+		assert_eq!(settings.window.display, Display::Lcd);
+		assert_eq!(settings.window.surface_size, Some((1280, 720)));
+		assert_eq!(settings.window.alpha.map(|a| { *a }), Some(8));
+		assert_eq!(*settings.window.red, 8);
+		assert!(settings.window.depth.is_none());
+		let again = Settings::from_toml(&settings.to_toml().unwrap()).unwrap();
+		assert_eq!(again.window.surface_size, Some((1280, 720)));
+		assert!(Settings::from_toml("[window]\ndisplay = \"vga\"\n").is_err());
+	}
+
+	// The environment is shared by all threads, so every override is tested by one test.
+	#[test]
+	fn env_overrides() {
+		for name in VARS.iter() { env::remove_var(name); }
+		let mut settings = Settings::from_toml("[window]\ndisplay = \"lcd\"\ndepth = 24\n").unwrap();
+		settings.apply_env().unwrap();
+		assert_eq!(settings.window.display, Display::Lcd);
+
+		env::set_var("GLIUM_PIB_LIB_DIR", "/usr/lib/arm-linux-gnueabihf");
+		env::set_var("GLIUM_PIB_DISPLAY", "HDMI");
+		env::set_var("GLIUM_PIB_SURFACE_SIZE", "640 x 480");
+		env::set_var("GLIUM_PIB_SCALING", "fit");
+		env::set_var("GLIUM_PIB_RED_BITS", "5");
+		env::set_var("GLIUM_PIB_GREEN_BITS", "6");
+		env::set_var("GLIUM_PIB_BLUE_BITS", "5");
+		env::set_var("GLIUM_PIB_ALPHA_BITS", "8");
+		env::set_var("GLIUM_PIB_DEPTH_BITS", "0");
+		settings.apply_env().unwrap();
+		assert_eq!(&*settings.lib_dir, Path::new("/usr/lib/arm-linux-gnueabihf"));
+		assert_eq!(settings.window.display, Display::Hdmi);
+		assert_eq!(settings.window.surface_size, Some((640, 480)));
+		assert_eq!(settings.window.scaling, Scaling::Fit);
+		assert_eq!((*settings.window.red, *settings.window.green, *settings.window.blue), (5, 6, 5));
+		assert_eq!(settings.window.alpha.map(|a| { *a }), Some(8));
+		assert!(settings.window.depth.is_none());
+
+		let invalid = [
+			("GLIUM_PIB_DISPLAY", "vga"),
+			("GLIUM_PIB_SURFACE_SIZE", "640"),
+			("GLIUM_PIB_SCALING", "zoom"),
+			("GLIUM_PIB_RED_BITS", "eight"),
+		];
+		for &(name, value) in invalid.iter() {
+			let old = env::var(name).unwrap();
+			env::set_var(name, value);
+			assert!(Settings::from_env().is_err(), "{}={} was accepted", name, value);
+			env::set_var(name, old);
+		}
+		for name in VARS.iter() { env::remove_var(name); }
+	}
+}
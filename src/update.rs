@@ -0,0 +1,271 @@
+use std::cell::Cell;
+use std::mem;
+use std::ops::Deref;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use libc;
+
+use ffi;
+use error::Error;
+use config::{Rect, FixedRect, SurfaceMapping, WindowConfig};
+use layer::{self, DispmanxImageLayer};
+use dispmanx::{self, vc_rect, fixed_vc_rect};
+use super::{System, Window, NativeWindow};
+
+/// Changes of the attributes of a dispmanx element. Attributes which are none are kept.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ElementChange {
+	/// The layer of the element. Elements of higher layers are shown above.
+	pub layer: Option<i32>,
+	/// The opacity of the element, 255 is opaque.
+	pub opacity: Option<u8>,
+	/// The rectangle of the display the element is shown in.
+	pub destination: Option<Rect>,
+	/// The part of the source which is shown, e.g. to zoom or pan.
+	pub source: Option<FixedRect>,
+}
+impl ElementChange {
+	/// Change the layer.
+	pub fn with_layer(mut self, layer: i32) -> Self {
+		self.layer = Some(layer);
+		self
+	}
+	/// Change the opacity.
+	pub fn with_opacity(mut self, opacity: u8) -> Self {
+		self.opacity = Some(opacity);
+		self
+	}
+	/// Change the rectangle of the display.
+	pub fn with_destination(mut self, destination: Rect) -> Self {
+		self.destination = Some(destination);
+		self
+	}
+	/// Change the part of the source shown.
+	pub fn with_source(mut self, source: FixedRect) -> Self {
+		self.source = Some(source);
+		self
+	}
+}
+
+/// A dispmanx update: element changes of windows and image layers which are shown together at the next vsync.
+///
+/// Only supported on the dispmanx platform. Created by `System::start_update`.
+/// Windows can be added and removed with an update as well, e.g. to replace one window by another without a frame in between.
+/// An update which is dropped without being submitted is submitted synchronously.
+pub struct Update<'a> {
+	/// The system.
+	system: &'a System,
+	/// The Bcm-Host library of the system.
+	lib_bcm_host: &'a ffi::LibBcmHost,
+	/// The update. DISPMANX_NO_HANDLE once submitted.
+	handle: ffi::DispmanxUpdateHandle,
+	/// The surfaces of changed windows with their new mapping, set once the update is submitted.
+	mappings: Vec<(&'a dispmanx::Surface, SurfaceMapping)>,
+	/// The surfaces of removed windows, closed once the update is submitted.
+	removed: Vec<dispmanx::Surface>,
+}
+impl System {
+	/// Start a dispmanx update.
+	pub fn start_update(&self) -> Result<Update, Error> {
+		let lib_bcm_host = try!(self.lib_bcm_host());
+		let _guard = self.mutex.lock().unwrap();
+		let handle = unsafe { (lib_bcm_host.vc_dispmanx_update_start)(0) };
+		if handle == ffi::DISPMANX_NO_HANDLE { return Err(Error::Fn("vc_dispmanx_update_start")); }
+		Ok(Update {
+			system: self,
+			lib_bcm_host: lib_bcm_host,
+			handle: handle,
+			mappings: Vec::new(),
+			removed: Vec::new(),
+		})
+	}
+}
+impl<'a> Update<'a> {
+	/// Show an image layer scaled to a rectangle of the display on a layer. Replaces the element if the image is already shown.
+	pub fn show_layer<S>(&mut self, image_layer: &mut DispmanxImageLayer<S>, layer: i32, destination: Rect) -> Result<(), Error> where S: Deref<Target=System> {
+		try!(self.check_system(&*image_layer.system));
+		let _guard = self.system.mutex.lock().unwrap();
+		unsafe { layer::add_element(image_layer, self.lib_bcm_host, self.handle, layer, destination) }
+	}
+	/// Hide an image layer. Does nothing if the image is hidden.
+	pub fn hide_layer<S>(&mut self, image_layer: &mut DispmanxImageLayer<S>) -> Result<(), Error> where S: Deref<Target=System> {
+		try!(self.check_system(&*image_layer.system));
+		let _guard = self.system.mutex.lock().unwrap();
+		unsafe { layer::remove_element(image_layer, self.lib_bcm_host, self.handle) }
+	}
+	/// Change the attributes of a shown image layer.
+	pub fn change_layer<S>(&mut self, image_layer: &DispmanxImageLayer<S>, change: &ElementChange) -> Result<(), Error> where S: Deref<Target=System> {
+		try!(self.check_system(&*image_layer.system));
+		if !image_layer.is_shown() { return Err(Error::Unsupported("the image layer is hidden")); }
+		self.change_element(layer::element(image_layer), change)
+	}
+	/// Change the attributes of the element of a window. The size of the surface stays the same, the source is scaled to the destination.
+	/// The surface mapping of the window is changed once the update is submitted.
+	pub fn change_window<S>(&mut self, window: &'a Window<S>, change: &ElementChange) -> Result<(), Error> where S: Deref<Target=System> {
+		try!(self.check_system(&*window.system));
+		let surface = match window.native_window {
+			NativeWindow::Dispmanx(ref surface) => surface,
+			_ => return Err(Error::Unsupported("the window has no dispmanx element")),
+		};
+		try!(self.change_element(surface.element(), change));
+		let mut mapping = surface.mapping();
+		mapping.source = change.source.unwrap_or(mapping.source);
+		mapping.destination = change.destination.unwrap_or(mapping.destination);
+		self.mappings.push((surface, mapping));
+		Ok(())
+	}
+	/// Create a window whose element is shown with the update. The context and the surface are created right away.
+	pub fn add_window<S>(&mut self, system: S, config: &WindowConfig) -> Result<Window<S>, Error> where S: Deref<Target=System> {
+		try!(self.check_system(&*system));
+		let mut window = Window::empty(system);
+		match window.initialize(config, false, ffi::EGL_NO_CONTEXT, Some(self.handle)) {
+			Ok(()) => Ok(window),
+			Err(e) => {
+				// Elements added in the update can only be removed in it.
+				let _ = self.remove_window(window);
+				Err(e)
+			},
+		}
+	}
+	/// Remove a window. The context and the surface are destroyed right away, the element is removed with the update.
+	pub fn remove_window<S>(&mut self, mut window: Window<S>) -> Result<(), Error> where S: Deref<Target=System> {
+		try!(self.check_system(&*window.system));
+		let mut surface = match mem::replace(&mut window.native_window, NativeWindow::None) {
+			NativeWindow::Dispmanx(surface) => surface,
+			native_window => {
+				window.native_window = native_window;
+				return Ok(());
+			},
+		};
+		// The EGL surface must be destroyed before the element.
+		drop(window);
+		let _guard = self.system.mutex.lock().unwrap();
+		let result = unsafe { surface.remove_elements(self.lib_bcm_host, self.handle) };
+		self.removed.push(surface);
+		result
+	}
+	/// Submit the update and wait until it is shown.
+	pub fn submit(mut self) -> Result<(), Error> {
+		let handle = self.handle;
+		self.handle = ffi::DISPMANX_NO_HANDLE;
+		let _guard = self.system.mutex.lock().unwrap();
+		let submitted = unsafe { (self.lib_bcm_host.vc_dispmanx_update_submit_sync)(handle) } == ffi::DISPMANX_SUCCESS;
+		unsafe { self.finish(submitted); }
+		if !submitted { return Err(Error::Fn("vc_dispmanx_update_submit_sync")); }
+		Ok(())
+	}
+	/// Submit the update without waiting. The callback is called on a thread of the VideoCore library once the update is shown.
+	pub fn submit_with_callback<F>(mut self, callback: F) -> Result<(), Error> where F: FnOnce() + Send + 'static {
+		let handle = self.handle;
+		self.handle = ffi::DISPMANX_NO_HANDLE;
+		let _guard = self.system.mutex.lock().unwrap();
+		let callback = Box::into_raw(Box::new(callback));
+		let submitted = unsafe { (self.lib_bcm_host.vc_dispmanx_update_submit)(handle, update_callback::<F>, callback as *mut libc::c_void) } == ffi::DISPMANX_SUCCESS;
+		unsafe { self.finish(submitted); }
+		if !submitted {
+			drop(unsafe { Box::from_raw(callback) });
+			return Err(Error::Fn("vc_dispmanx_update_submit"));
+		}
+		Ok(())
+	}
+	/// Submit the update without waiting. The returned handle tells when the update is shown, so a render loop can go on meanwhile.
+	pub fn submit_async(self) -> Result<PendingUpdate, Error> {
+		let (sender, receiver) = mpsc::channel();
+		try!(self.submit_with_callback(move || { let _ = sender.send(()); }));
+		Ok(PendingUpdate {
+			receiver: receiver,
+			shown: Cell::new(false),
+		})
+	}
+	// Set the mappings of changed windows if the update was submitted and close the surfaces of removed windows. The mutex of the system must be locked.
+	unsafe fn finish(&mut self, submitted: bool) {
+		if submitted {
+			for &(surface, mapping) in self.mappings.iter() { surface.set_mapping(mapping); }
+		}
+		self.mappings.clear();
+		for mut surface in self.removed.drain(..) { surface.close(self.lib_bcm_host); }
+	}
+	// Fail if an object belongs to another system.
+	fn check_system(&self, system: &System) -> Result<(), Error> {
+		if system as *const System != self.system as *const System { return Err(Error::Unsupported("only elements of the system of the update can be changed")); }
+		Ok(())
+	}
+	// Change the attributes of an element.
+	fn change_element(&mut self, element: ffi::DispmanxElementHandle, change: &ElementChange) -> Result<(), Error> {
+		let mut flags = 0;
+		if change.layer.is_some() { flags |= ffi::DISPMANX_ELEMENT_CHANGE_LAYER; }
+		if change.opacity.is_some() { flags |= ffi::DISPMANX_ELEMENT_CHANGE_OPACITY; }
+		let dest_rect = match change.destination {
+			Some(destination) => {
+				flags |= ffi::DISPMANX_ELEMENT_CHANGE_DEST_RECT;
+				vc_rect(destination)
+			},
+			None => vc_rect(Rect::default()),
+		};
+		let src_rect = match change.source {
+			Some(source) => {
+				flags |= ffi::DISPMANX_ELEMENT_CHANGE_SRC_RECT;
+				fixed_vc_rect(source)
+			},
+			None => fixed_vc_rect(FixedRect::default()),
+		};
+		let _guard = self.system.mutex.lock().unwrap();
+		if unsafe {
+			(self.lib_bcm_host.vc_dispmanx_element_change_attributes)(
+				self.handle, element,
+				flags, change.layer.unwrap_or(0), change.opacity.unwrap_or(255),
+				&dest_rect as *const ffi::VcRect, &src_rect as *const ffi::VcRect,
+				ffi::DISPMANX_NO_HANDLE, 0
+			)
+		} != ffi::DISPMANX_SUCCESS { return Err(Error::Fn("vc_dispmanx_element_change_attributes")); }
+		Ok(())
+	}
+}
+impl<'a> Drop for Update<'a> {
+	fn drop(&mut self) {
+		if self.handle != ffi::DISPMANX_NO_HANDLE {
+			// Dispmanx can not abort an update.
+			let _guard = self.system.mutex.lock().unwrap();
+			unsafe {
+				let submitted = (self.lib_bcm_host.vc_dispmanx_update_submit_sync)(self.handle) == ffi::DISPMANX_SUCCESS;
+				self.finish(submitted);
+			}
+			self.handle = ffi::DISPMANX_NO_HANDLE;
+		}
+	}
+}
+
+/// An update submitted by `Update::submit_async` which may not be shown yet.
+pub struct PendingUpdate {
+	/// Receives a message once the update is shown.
+	receiver: Receiver<()>,
+	/// Whether the message was received.
+	shown: Cell<bool>,
+}
+impl PendingUpdate {
+	/// Whether the update is shown. Does not block.
+	pub fn is_shown(&self) -> bool {
+		if !self.shown.get() {
+			match self.receiver.try_recv() {
+				Ok(()) | Err(TryRecvError::Disconnected) => self.shown.set(true),
+				Err(TryRecvError::Empty) => {},
+			}
+		}
+		self.shown.get()
+	}
+	/// Block until the update is shown.
+	pub fn wait(self) {
+		if !self.shown.get() { let _ = self.receiver.recv(); }
+	}
+	/// The channel which receives a message once the update is shown, e.g. to select on it with other events.
+	/// Receives nothing if `is_shown` returned true before.
+	pub fn into_receiver(self) -> Receiver<()> {
+		self.receiver
+	}
+}
+
+// Called by the VideoCore library once an update submitted with a callback is shown.
+extern "C" fn update_callback<F>(_update: ffi::DispmanxUpdateHandle, arg: *mut libc::c_void) where F: FnOnce() + Send + 'static {
+	let callback = unsafe { Box::from_raw(arg as *mut F) };
+	callback();
+}